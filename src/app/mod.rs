@@ -1,25 +1,120 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fs;
 use std::io::{self, Stdout};
-use std::path::{self, PathBuf};
+use std::path::{self, Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
 use std::time::{Duration, Instant};
 
 use exitfailure::ExitFailure;
 use rand::prelude::SliceRandom;
-use rodio::{OutputStreamHandle, Sink};
+use rodio::cpal::traits::HostTrait;
+use rodio::{cpal, DeviceTrait, OutputStream, OutputStreamHandle, Sink, Source};
+use serde::{Deserialize, Serialize};
 use tui::backend::CrosstermBackend;
+use tui::layout::Rect;
 use tui::Terminal;
 
-use crate::commands::process_command;
-use crate::file_ops::{self, get_audio_source, DirectoryItem};
+use crate::command_line::CommandLine;
+use crate::commands::{self, process_command};
+use crate::config::InitConfig;
+use crate::cover::CoverArt;
+use crate::dupes::{self, Report};
+use crate::file_ops::{
+    self, get_audio_source, write_audio_tags, DirectoryItem, FileFilter, TagChanges,
+};
+use crate::history::History;
+use crate::keymap::{Key, Keymap};
+use crate::library::Library;
+use crate::lyrics::LyricsPanel;
 use crate::music::Music;
+use crate::organize::{self, Move, Preview};
+use crate::scrobbler::log::ScrobbleLog;
+use crate::scrobbler::Scrobbler;
+use crate::session::{self, Session};
+use crate::smart::{Query, Track};
+use crate::sort::Sorter;
+use crate::stats::{ResumeFiles, ResumePosition, Stats};
+use crate::tag_editor::TagEditor;
+use crate::utils::split_path::expand_home;
+use crate::utils::timestamp::now_timestamp;
+use crate::visualizer::Visualizer;
+use crate::waveform::Waveforms;
 
 #[derive(PartialEq)]
 pub enum Mode {
     Browse,
     Search,
     Command,
+    TagEdit,
+    Organize,
+    Help,
+    Delete,
 }
 
+// The pane the movement keys act on
+#[derive(PartialEq)]
+pub enum Focus {
+    Browser,
+    PlayList,
+}
+
+// Music of a background read, and the last error
+type ReadMusic = (Vec<Music>, Option<String>);
+
+// Where added music goes in the play list
+#[derive(Clone, Copy)]
+pub enum Insert {
+    // In front, replacing the playing music
+    Now,
+    // In front, after the playing music
+    Next,
+    End,
+    // Before the music at this index of the play list
+    At(usize),
+}
+
+// What was asked for while the library was read, it is run once the library is there
+enum LibraryUse {
+    Smart(String),
+    Dupes(bool),
+}
+
+// Where the parts that take clicks were last drawn
+#[derive(Clone, Copy, Default)]
+pub struct MouseAreas {
+    // Rows of the browser and the index of the first one
+    pub music_list: Option<(Rect, usize)>,
+    // Rows of the play list, the playing music counting as the first row
+    pub play_list: Option<(Rect, usize)>,
+    pub seek_bar: Option<Rect>,
+    pub volume: Option<Rect>,
+}
+
+// Files waiting for confirmation before they are deleted, and how far the list is scrolled
+pub struct DeletePreview {
+    pub paths: Vec<String>,
+    pub scroll: usize,
+}
+
+fn contains(area: Rect, column: u16, row: u16) -> bool {
+    column >= area.x && column < area.x + area.width && row >= area.y && row < area.y + area.height
+}
+
+pub fn output_device_names() -> Vec<String> {
+    match cpal::default_host().output_devices() {
+        Ok(devices) => devices.filter_map(|device| device.name().ok()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+// A second click on the same cell this soon is a double click
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum PlayStyle {
+    #[default]
     PlayOrder,
     SingleCycle,
 }
@@ -30,15 +125,68 @@ pub struct App<'a> {
     pub current_directory: path::PathBuf,
     pub directory_contents: Vec<DirectoryItem>,
     pub search_buffer: Vec<char>,
-    pub command_buffer: Vec<char>,
+    pub command_line: CommandLine,
     pub error: Option<String>,
+    // A message that is not an error, like the answer of `volume`
+    pub info: Option<String>,
     pub window_height: u16,
     pub play_music_list: Vec<Music>,
     pub playing_music: Option<Music>,
     pub stream_handle: OutputStreamHandle,
+    // The stream of a device chosen with `device`, the default one is kept by main
+    output_stream: Option<OutputStream>,
     pub player: Sink,
     pub mode: Mode,
     pub play_style: PlayStyle,
+    pub music_database: String,
+    pub smart_playlists: BTreeMap<String, String>,
+    pub library: Option<Library>,
+    // The library being read in the background, and what waits for it
+    scanning: Option<Receiver<Result<Library, io::Error>>>,
+    waiting_for_library: Vec<LibraryUse>,
+    pub stats: Stats,
+    resume_files: Option<ResumeFiles>,
+    pub scrobble_log: Option<ScrobbleLog>,
+    pub scrobbler: Option<Scrobbler>,
+    pub tag_editor: Option<TagEditor>,
+    pub organize_preview: Option<Preview>,
+    pub delete_preview: Option<DeletePreview>,
+    // First line of the help shown with `?`
+    pub help_scroll: usize,
+    // Which files are listed and played
+    pub file_filter: FileFilter,
+    // Order of the folders and files of the browser
+    pub sorter: Sorter,
+    // While set, the browser lists duplicates instead of the current folder
+    pub dupes_report: Option<Report>,
+    // Duplicates being looked for in the background, comparing the audio takes a while
+    finding_dupes: Option<Receiver<Report>>,
+    pub cover_art: CoverArt,
+    pub lyrics_panel: LyricsPanel,
+    pub visualizer: Visualizer,
+    pub waveforms: Waveforms,
+    pub mouse_areas: MouseAreas,
+    // When and where the last click was
+    last_click: Option<(Instant, u16, u16)>,
+    // The volume before muting
+    muted_volume: Option<f32>,
+    pub keymap: Keymap,
+    pub focus: Focus,
+    // Paths of the marked files and folders, in any folder
+    pub marked_paths: BTreeSet<String>,
+    // Where the visual selection started, it ends at the selection
+    pub visual_start: Option<usize>,
+    // Folders being read in the background, queued in the order they were added
+    enqueueing: VecDeque<(Insert, Receiver<ReadMusic>)>,
+    // Play lists before the edits made to them
+    play_list_history: History<Vec<Music>>,
+    // Row of the play list cursor, the playing music is the first row
+    pub play_list_selection: usize,
+    // Keys of a sequence typed so far, and when the last one was
+    pub pending_keys: Vec<Key>,
+    pub pending_keys_time: Instant,
+    // When the session was last saved
+    session_time: Instant,
 
     max_file_selection: usize,
 }
@@ -46,13 +194,14 @@ pub struct App<'a> {
 impl<'a> App<'a> {
     pub fn new(
         terminal: &'a mut Terminal<CrosstermBackend<Stdout>>,
-        music_database: &str,
+        config: &InitConfig,
         stream_handle: OutputStreamHandle,
     ) -> Result<App<'a>, ExitFailure> {
         let window_height = terminal.size().unwrap().height - 5;
-        let current_directory = path::PathBuf::from(music_database);
+        let current_directory = path::PathBuf::from(&config.music_database);
 
         let player = Sink::try_new(&stream_handle)?; // Music player
+        let (keymap, keys_error) = Keymap::new(&config.keys);
 
         let mut app = App {
             terminal,
@@ -60,25 +209,68 @@ impl<'a> App<'a> {
             current_directory,
             directory_contents: Vec::new(),
             search_buffer: Vec::new(),
-            command_buffer: Vec::new(),
+            command_line: CommandLine::new(),
             error: None,
+            info: None,
             window_height,
             play_music_list: Vec::new(),
             playing_music: None,
             stream_handle,
+            output_stream: None,
             player,
             mode: Mode::Browse,
             play_style: PlayStyle::PlayOrder,
+            music_database: config.music_database.clone(),
+            smart_playlists: config.smart_playlists.clone(),
+            library: None,
+            scanning: None,
+            waiting_for_library: Vec::new(),
+            stats: Stats::load(),
+            resume_files: config.resume_files.as_ref().map(ResumeFiles::new),
+            scrobble_log: config.scrobbler_log.as_deref().map(ScrobbleLog::new),
+            scrobbler: config.scrobbler.as_ref().map(Scrobbler::new),
+            tag_editor: None,
+            organize_preview: None,
+            delete_preview: None,
+            help_scroll: 0,
+            file_filter: FileFilter::new(&config.browser),
+            sorter: Sorter::new(config.sort),
+            dupes_report: None,
+            finding_dupes: None,
+            cover_art: CoverArt::new(config.cover_art),
+            lyrics_panel: LyricsPanel::new(),
+            visualizer: Visualizer::new(),
+            waveforms: Waveforms::new(),
+            mouse_areas: MouseAreas::default(),
+            last_click: None,
+            muted_volume: None,
+            keymap,
+            focus: Focus::Browser,
+            marked_paths: BTreeSet::new(),
+            visual_start: None,
+            enqueueing: VecDeque::new(),
+            play_list_history: History::new(),
+            play_list_selection: 0,
+            pending_keys: Vec::new(),
+            pending_keys_time: Instant::now(),
+            session_time: Instant::now(),
             max_file_selection: 0,
         };
 
         app.populate_files()?;
+        app.restore_session(config.resume);
+        if keys_error.is_some() {
+            app.error = keys_error;
+        }
 
         Ok(app)
     }
 
+    // A new sink would start at full volume, it keeps the current one, 0 while muted
     fn new_sink(&mut self) -> Result<(), ExitFailure> {
+        let volume = self.player.volume();
         self.player = Sink::try_new(&self.stream_handle)?;
+        self.player.set_volume(volume);
         Ok(())
     }
 
@@ -94,17 +286,19 @@ impl<'a> App<'a> {
         self.search_buffer.push(char);
     }
 
-    pub fn add_to_command_buffer(&mut self, char: char) {
-        self.command_buffer.push(char);
-    }
-
     pub fn update_window_height(&mut self) {
         self.window_height = self.terminal.size().unwrap().height - 5;
     }
 
     pub fn populate_files(&mut self) -> Result<(), io::Error> {
+        self.visual_start = None;
+        if let Some(report) = &self.dupes_report {
+            self.populate_dupes(report.paths());
+            return Ok(());
+        }
+
         let mut dir_items = file_ops::get_files_for_current_directory(self)?;
-        dir_items.sort();
+        self.sorter.sort(&mut dir_items);
 
         self.directory_contents = dir_items;
         self.max_file_selection = self.directory_contents.len();
@@ -118,9 +312,28 @@ impl<'a> App<'a> {
         Ok(())
     }
 
+    pub fn toggle_hidden(&mut self) {
+        self.file_filter.toggle_hidden();
+        if let Err(err) = self.populate_files() {
+            self.error = Some(err.to_string());
+        }
+    }
+
+    // Keep the selection where it was, the report only shrinks as files are deleted
+    fn populate_dupes(&mut self, paths: Vec<String>) {
+        self.directory_contents = paths.into_iter().map(DirectoryItem::File).collect();
+        self.max_file_selection = self.directory_contents.len();
+
+        self.selection_index = match (self.max_file_selection, self.selection_index) {
+            (0, _) => None,
+            (max, Some(index)) => Some(index.min(max - 1)),
+            (_, None) => Some(0),
+        };
+    }
+
     pub fn populate_search_file(&mut self, astrict: &str) -> Result<(), io::Error> {
         let mut dir_items = file_ops::get_files_for_current_directory_astrict(self, astrict)?;
-        dir_items.sort();
+        self.sorter.sort(&mut dir_items);
 
         self.directory_contents = dir_items;
         self.max_file_selection = self.directory_contents.len();
@@ -143,15 +356,6 @@ impl<'a> App<'a> {
         search_string.trim().to_string()
     }
 
-    pub fn get_command_strign(&mut self) -> String {
-        let mut command_string = String::new();
-        for c in &self.command_buffer {
-            command_string.push(*c);
-        }
-
-        command_string
-    }
-
     pub fn get_selected_directory_item(&mut self) -> Option<DirectoryItem> {
         if let Some(index) = self.selection_index {
             Some(self.directory_contents[index].clone())
@@ -160,6 +364,97 @@ impl<'a> App<'a> {
         }
     }
 
+    // The marked items and the visual selection, or else the selected item
+    pub fn get_selected_directory_items(&mut self) -> Vec<DirectoryItem> {
+        let mut paths = self.marked_paths.clone();
+        if let Some(range) = self.visual_range() {
+            for item in &self.directory_contents[range] {
+                paths.insert(item.path().to_string());
+            }
+        }
+        if paths.is_empty() {
+            return self.get_selected_directory_item().into_iter().collect();
+        }
+
+        paths
+            .into_iter()
+            .map(|path| {
+                if Path::new(&path).is_dir() {
+                    DirectoryItem::Directory(path)
+                } else {
+                    DirectoryItem::File(path)
+                }
+            })
+            .collect()
+    }
+
+    // Paths of the files the selection stands for, a folder stands for everything inside it
+    pub fn get_selected_paths(&mut self) -> Vec<String> {
+        let mut paths = Vec::new();
+        for item in self.get_selected_directory_items() {
+            match item {
+                DirectoryItem::File(path) => paths.push(path),
+                DirectoryItem::Directory(path) => {
+                    match file_ops::get_audio_files_recursive(
+                        &PathBuf::from(path),
+                        &self.file_filter,
+                    ) {
+                        Ok(files) => paths.extend(files),
+                        Err(err) => self.error = Some(err.to_string()),
+                    }
+                }
+            }
+        }
+
+        paths
+    }
+
+    fn visual_range(&self) -> Option<std::ops::RangeInclusive<usize>> {
+        let (start, end) = (self.visual_start?, self.selection_index?);
+        let last = self.directory_contents.len().checked_sub(1)?;
+        Some(start.min(end).min(last)..=start.max(end).min(last))
+    }
+
+    pub fn is_marked(&self, index: usize) -> bool {
+        let in_visual = self
+            .visual_range()
+            .is_some_and(|range| range.contains(&index));
+        in_visual
+            || self
+                .directory_contents
+                .get(index)
+                .is_some_and(|item| self.marked_paths.contains(item.path()))
+    }
+
+    // Mark or unmark the selected item and go to the next one
+    pub fn toggle_mark(&mut self) {
+        if let Some(item) = self.get_selected_directory_item() {
+            let path = item.path().to_string();
+            if !self.marked_paths.remove(&path) {
+                self.marked_paths.insert(path);
+            }
+            self.move_select_down(1);
+        }
+    }
+
+    // Start a visual selection, or mark what it covers and end it
+    pub fn toggle_visual(&mut self) {
+        match self.visual_range() {
+            Some(range) => {
+                for item in &self.directory_contents[range] {
+                    self.marked_paths.insert(item.path().to_string());
+                }
+                self.visual_start = None;
+            }
+            None => self.visual_start = self.selection_index,
+        }
+    }
+
+    pub fn clear_marks(&mut self) {
+        self.marked_paths.clear();
+        self.visual_start = None;
+    }
+
     pub fn move_select_top(&mut self) {
         if let Some(_) = self.selection_index {
             self.selection_index = Some(0);
@@ -262,6 +557,11 @@ impl<'a> App<'a> {
     }
 
     pub fn back_previous_folder(&mut self, music_database: &str) {
+        if self.dupes_report.is_some() {
+            self.close_dupes();
+            return;
+        }
+
         let music_database = PathBuf::from(music_database);
         let current_directory = self.current_directory.clone();
 
@@ -284,33 +584,100 @@ impl<'a> App<'a> {
         }
     }
 
-    pub fn add_music_to_list(&mut self) {
+    pub fn add_music_to_list(&mut self, insert: Insert) {
+        if !self.marked_paths.is_empty() || self.visual_start.is_some() {
+            let items = self.get_selected_directory_items();
+            self.enqueue_in_background(items, insert);
+            self.clear_marks();
+            return;
+        }
+
         match self.get_selected_directory_item() {
             Some(dir_item) => match dir_item {
                 DirectoryItem::File(path) => {
                     match Music::new(&path) {
-                        Ok(music) => self.play_music_list.push(music),
+                        Ok(music) => self.insert_music(vec![music], insert),
                         Err(err) => self.error = Some(err),
                     };
                 }
-                DirectoryItem::Directory(_) => self.error = Some(String::from("Is a directory")),
+                DirectoryItem::Directory(path) => {
+                    self.enqueue_in_background(vec![DirectoryItem::Directory(path)], insert)
+                }
             },
             None => {}
         };
     }
 
+    pub fn insert_music(&mut self, music_list: Vec<Music>, insert: Insert) {
+        if music_list.is_empty() {
+            return;
+        }
+        // Playing now leaves the play list as it was
+        if !matches!(insert, Insert::Now) {
+            self.save_play_list();
+        }
+        let index = match insert {
+            Insert::Now | Insert::Next => 0,
+            Insert::End => self.play_music_list.len(),
+            Insert::At(index) => index.min(self.play_music_list.len()),
+        };
+
+        // The cursor stays on the same music
+        let count = music_list.len();
+        let first_row = index + self.playing_music.is_some() as usize;
+        if self.play_list_selection >= first_row && self.play_list_selection < self.play_list_rows()
+        {
+            self.play_list_selection += count;
+        }
+        self.play_music_list.splice(index..index, music_list);
+
+        if let Insert::Now = insert {
+            self.play_next_music();
+            self.play_list_selection = 0;
+        }
+    }
+
+    // Read the tags of everything in `items` without blocking the interface
+    pub fn enqueue_in_background(&mut self, items: Vec<DirectoryItem>, insert: Insert) {
+        let (sender, receiver) = mpsc::channel();
+        let filter = self.file_filter.clone();
+        thread::spawn(move || {
+            let _ = sender.send(file_ops::read_music_recursive(&items, &filter));
+        });
+        self.enqueueing.push_back((insert, receiver));
+    }
+
+    fn poll_enqueueing(&mut self) {
+        while let Some((insert, receiver)) = self.enqueueing.front() {
+            let insert = *insert;
+            match receiver.try_recv() {
+                Ok((music_list, error)) => {
+                    self.insert_music(music_list, insert);
+                    if error.is_some() {
+                        self.error = error;
+                    }
+                }
+                Err(mpsc::TryRecvError::Empty) => return,
+                Err(mpsc::TryRecvError::Disconnected) => {}
+            }
+            self.enqueueing.pop_front();
+        }
+    }
+
     pub fn add_all_music_to_list(&mut self) {
+        let mut music_list = Vec::new();
         for item in &self.directory_contents {
             match item {
                 DirectoryItem::File(path) => {
                     match Music::new(&path) {
-                        Ok(music) => self.play_music_list.push(music),
+                        Ok(music) => music_list.push(music),
                         Err(err) => self.error = Some(err),
                     };
                 }
                 _ => {}
             }
         }
+        self.insert_music(music_list, Insert::End);
     }
 
     pub fn stop_or_start_play(&mut self) {
@@ -326,20 +693,62 @@ impl<'a> App<'a> {
         }
     }
 
+    // Count the playing music as played, or as skipped if it was left early
+    fn finish_playing_music(&mut self, completed: bool) {
+        if let Some(music) = &mut self.playing_music {
+            if !music.play_recorded {
+                music.play_recorded = true;
+                let result = if completed {
+                    self.stats.record_play(&music.path)
+                } else {
+                    self.stats.record_skip(&music.path)
+                };
+                if let Err(err) = result {
+                    self.error = Some(err);
+                }
+
+                let started_at = now_timestamp().saturating_sub(music.play_position.as_secs());
+                if let Some(scrobble_log) = &self.scrobble_log {
+                    if let Err(err) = scrobble_log.append(music, completed, started_at) {
+                        self.error = Some(err);
+                    }
+                }
+                if let Some(scrobbler) = &self.scrobbler {
+                    if completed {
+                        scrobbler.listen(music, started_at);
+                    }
+                }
+            }
+        }
+    }
+
     pub fn play_next_music(&mut self) {
+        // The cursor stays on the same music as the rows move up
+        if self.playing_music.is_some() {
+            self.play_list_selection = self.play_list_selection.saturating_sub(1);
+        }
+        self.save_resume_position(self.player.empty());
+        self.finish_playing_music(self.player.empty());
         if !self.player.empty() {
             self.new_sink().unwrap();
         }
         if self.play_music_list.len() > 0 {
             match get_audio_source(&self.play_music_list[0].path) {
                 Ok(source) => {
-                    self.player.append(source);
+                    self.player.append(self.visualizer.tap(source));
                     let mut music = self.play_music_list.remove(0);
                     music.start_time = Some(Instant::now());
+                    if let Some(scrobbler) = &self.scrobbler {
+                        scrobbler.now_playing(&music);
+                    }
+                    let position = self.resume_position(&music);
                     self.playing_music = Some(music);
+                    if let Some(position) = position {
+                        self.seek(position);
+                    }
                 }
                 Err(err) => {
-                    self.error = Some(err.to_string());
+                    self.error = Some(err);
                     self.playing_music = None;
                 }
             }
@@ -349,22 +758,32 @@ impl<'a> App<'a> {
     }
 
     pub fn update_volume(&mut self, f: &dyn Fn(f32) -> f32) {
+        self.muted_volume = None;
         self.player.set_volume(f(self.player.volume()));
     }
 
     pub fn check_music_list(&mut self) {
+        self.poll_enqueueing();
+        self.poll_scanning();
+        self.poll_finding_dupes();
         if self.player.empty() {
             match self.play_style {
                 PlayStyle::PlayOrder => self.play_next_music(),
                 PlayStyle::SingleCycle => {
+                    self.save_resume_position(true);
+                    self.finish_playing_music(true);
                     if let Some(playing_music) = &mut self.playing_music {
                         match get_audio_source(&playing_music.path) {
                             Ok(source) => {
                                 playing_music.play_position = Duration::from_secs(0);
-                                self.player.append(source);
+                                playing_music.play_recorded = false;
+                                self.player.append(self.visualizer.tap(source));
                                 playing_music.start_time = Some(Instant::now());
+                                if let Some(scrobbler) = &self.scrobbler {
+                                    scrobbler.now_playing(playing_music);
+                                }
                             }
-                            Err(err) => self.error = Some(err.to_string()),
+                            Err(err) => self.error = Some(err),
                         }
                     } else {
                         self.play_next_music();
@@ -376,12 +795,263 @@ impl<'a> App<'a> {
         if !self.player.is_paused() {
             if let Some(playing_music) = &mut self.playing_music {
                 playing_music.play_position = playing_music.start_time.unwrap().elapsed();
+                if !playing_music.play_recorded && playing_music.is_listened() {
+                    self.finish_playing_music(true);
+                }
+            }
+        }
+
+        if let Some(scrobbler) = &self.scrobbler {
+            if let Some(err) = scrobbler.poll_error() {
+                self.error = Some(err);
+            }
+        }
+
+        self.cover_art.update(self.playing_music.as_ref());
+        self.lyrics_panel.update(self.playing_music.as_ref());
+        self.waveforms.update(self.playing_music.as_ref());
+
+        if self.session_time.elapsed() >= session::SAVE_INTERVAL {
+            self.save_session();
+        }
+    }
+
+    pub fn save_session(&mut self) {
+        self.session_time = Instant::now();
+        self.save_resume_position(false);
+        let session = Session {
+            play_list: self
+                .play_music_list
+                .iter()
+                .map(|music| music.path.clone())
+                .collect(),
+            playing_music: self.playing_music.as_ref().map(|music| music.path.clone()),
+            position: self
+                .playing_music
+                .as_ref()
+                .map_or(0, |music| music.play_position.as_millis() as u64),
+            // Muting is not kept, the volume before it is
+            volume: self.muted_volume.unwrap_or(self.player.volume()),
+            play_style: self.play_style,
+        };
+        if let Err(err) = session.save() {
+            self.error = Some(err);
+        }
+    }
+
+    fn resumes(&self, music: &Music) -> bool {
+        self.resume_files
+            .as_ref()
+            .is_some_and(|resume_files| resume_files.applies(music))
+    }
+
+    // Where a long music was left, unless it was finished
+    fn resume_position(&self, music: &Music) -> Option<Duration> {
+        if !self.resumes(music) {
+            return None;
+        }
+        self.stats
+            .get(&music.path)
+            .resume
+            .map(|resume| Duration::from_millis(resume.position))
+    }
+
+    // Remember where the playing music is, or that it was heard to the end
+    fn save_resume_position(&mut self, finished: bool) {
+        let music = match &self.playing_music {
+            Some(music) if self.resumes(music) => music,
+            _ => return,
+        };
+        let result = if finished {
+            self.stats.set_finished(std::slice::from_ref(&music.path))
+        } else {
+            let resume = ResumePosition {
+                position: music.play_position.as_millis() as u64,
+                duration: music.total_duration.as_millis() as u64,
+            };
+            self.stats.set_resume(&music.path, resume)
+        };
+        if let Err(err) = result {
+            self.error = Some(err);
+        }
+    }
+
+    // Forget where the selected files were left, the playing one makes way for the next
+    pub fn mark_finished(&mut self) {
+        let paths = self.get_selected_paths();
+        let playing = self
+            .playing_music
+            .as_ref()
+            .is_some_and(|music| paths.contains(&music.path));
+        if playing {
+            self.play_next_music();
+        }
+        if let Err(err) = self.stats.set_finished(&paths) {
+            self.error = Some(err);
+        }
+        self.clear_marks();
+    }
+
+    // The music that was playing is loaded paused, at its last position when `resume` is set
+    fn restore_session(&mut self, resume: bool) {
+        let session = match Session::load() {
+            Some(session) => session,
+            None => return,
+        };
+        self.player.set_volume(session.volume);
+        self.play_style = session.play_style;
+        // Files that are gone since are left out
+        self.play_music_list = session
+            .play_list
+            .iter()
+            .filter_map(|path| Music::new(path).ok())
+            .collect();
+
+        let playing_music = session
+            .playing_music
+            .and_then(|path| Music::new(&path).ok());
+        if let Some(music) = playing_music {
+            let position = if resume {
+                Duration::from_millis(session.position)
+            } else {
+                self.resume_position(&music).unwrap_or_default()
+            };
+            self.player.pause();
+            self.playing_music = Some(music);
+            self.seek(position);
+            if self.player.empty() {
+                self.playing_music = None;
+            }
+        }
+    }
+
+    // Restart the playing music from `position`, keeping the volume and pause state
+    pub fn seek(&mut self, position: Duration) {
+        let (path, position) = match &self.playing_music {
+            Some(music) => (music.path.clone(), position.min(music.total_duration)),
+            None => return,
+        };
+
+        match get_audio_source(&path) {
+            Ok(source) => {
+                let paused = self.player.is_paused();
+                self.new_sink().unwrap();
+                if paused {
+                    self.player.pause();
+                }
+                self.player
+                    .append(self.visualizer.tap(source.skip_duration(position)));
+                if let Some(music) = &mut self.playing_music {
+                    music.play_position = position;
+                    music.start_time = Some(Instant::now() - position);
+                }
+            }
+            Err(err) => self.error = Some(err),
+        }
+    }
+
+    // A left click on the seek bar jumps to that point of the playing music and one on the
+    // volume mutes it, a row of the browser or the play list is selected and a double click
+    // adds or plays it
+    pub fn click(&mut self, column: u16, row: u16) {
+        let double_click = self
+            .last_click
+            .is_some_and(|(time, last_column, last_row)| {
+                time.elapsed() <= DOUBLE_CLICK && last_column == column && last_row == row
+            });
+        self.last_click = if double_click {
+            None
+        } else {
+            Some((Instant::now(), column, row))
+        };
+        let areas = self.mouse_areas;
+
+        if let Some(area) = areas.seek_bar.filter(|area| contains(*area, column, row)) {
+            if let Some(music) = &self.playing_music {
+                let ratio = (column - area.x) as f64 / area.width as f64;
+                self.seek(music.total_duration.mul_f64(ratio));
+            }
+        } else if areas.volume.is_some_and(|area| contains(area, column, row)) {
+            self.toggle_mute();
+        } else if let Some((area, first)) = areas
+            .music_list
+            .filter(|(area, _)| contains(*area, column, row))
+        {
+            let index = first + (row - area.y) as usize;
+            if index < self.directory_contents.len() {
+                self.focus = Focus::Browser;
+                self.selection_index = Some(index);
+                if double_click {
+                    self.add_music_to_list(Insert::End);
+                }
+            }
+        } else if let Some((area, first)) = areas
+            .play_list
+            .filter(|(area, _)| contains(*area, column, row))
+        {
+            let index = first + (row - area.y) as usize;
+            if index < self.play_list_rows() {
+                self.focus = Focus::PlayList;
+                self.play_list_selection = index;
+                if double_click {
+                    self.play_selected_now();
+                }
+            }
+        }
+    }
+
+    // The wheel pages through the browser or moves through the play list
+    pub fn scroll(&mut self, column: u16, row: u16, down: bool) {
+        let areas = self.mouse_areas;
+        if areas
+            .music_list
+            .is_some_and(|(area, _)| contains(area, column, row))
+        {
+            if down {
+                self.next_page();
+            } else {
+                self.previous_page();
+            }
+        } else if areas
+            .play_list
+            .is_some_and(|(area, _)| contains(area, column, row))
+        {
+            self.focus = Focus::PlayList;
+            let step = self.window_height as i32;
+            self.move_play_list_selection(if down { step } else { -step });
+        }
+    }
+
+    pub fn toggle_mute(&mut self) {
+        match self.muted_volume.take() {
+            Some(volume) => self.player.set_volume(volume),
+            None => {
+                self.muted_volume = Some(self.player.volume());
+                self.player.set_volume(0.0);
             }
         }
     }
 
+    // Rate the playing music, or the selected file when nothing is playing
+    pub fn rate_music(&mut self, rating: u8) {
+        let path = match &self.playing_music {
+            Some(music) => music.path.clone(),
+            None => match self.get_selected_directory_item() {
+                Some(DirectoryItem::File(path)) => path,
+                _ => {
+                    self.error = Some(String::from("Nothing to rate"));
+                    return;
+                }
+            },
+        };
+        if let Err(err) = self.stats.set_rating(&path, rating) {
+            self.error = Some(err);
+        }
+    }
+
     pub fn shuffle_playlist(&mut self) {
         if self.play_music_list.len() > 1 {
+            self.save_play_list();
             self.play_music_list.shuffle(&mut rand::thread_rng());
         }
     }
@@ -389,25 +1059,553 @@ impl<'a> App<'a> {
     pub fn remove_play_list_by_id(&mut self, mut to_remove: Vec<usize>) {
         to_remove.sort();
         to_remove.reverse();
+        to_remove.dedup();
+        to_remove.retain(|index| *index < self.play_music_list.len());
+        if !to_remove.is_empty() {
+            self.save_play_list();
+        }
         for index in to_remove {
-            if index <= self.play_music_list.len() {
+            self.play_music_list.remove(index);
+        }
+    }
+
+    pub fn switch_focus(&mut self) {
+        self.focus = match self.focus {
+            Focus::Browser => Focus::PlayList,
+            Focus::PlayList => Focus::Browser,
+        };
+    }
+
+    fn play_list_rows(&self) -> usize {
+        self.play_music_list.len() + self.playing_music.is_some() as usize
+    }
+
+    // Index in `play_music_list` of the selected row, None for the playing music
+    fn selected_play_list_index(&self) -> Option<usize> {
+        let offset = self.playing_music.is_some() as usize;
+        let selection = self
+            .play_list_selection
+            .min(self.play_list_rows().saturating_sub(1));
+        selection
+            .checked_sub(offset)
+            .filter(|index| *index < self.play_music_list.len())
+    }
+
+    pub fn move_play_list_selection(&mut self, step: i32) {
+        let last = self.play_list_rows().saturating_sub(1) as i32;
+        let selection = self.play_list_selection.min(last as usize) as i32 + step;
+        self.play_list_selection = selection.clamp(0, last.max(0)) as usize;
+    }
+
+    pub fn select_play_list_bottom(&mut self) {
+        self.play_list_selection = self.play_list_rows().saturating_sub(1);
+    }
+
+    pub fn jump_to_playing_music(&mut self) {
+        self.focus = Focus::PlayList;
+        self.play_list_selection = 0;
+    }
+
+    // Swap the selected music with the one `step` rows away, the playing music stays first
+    pub fn move_play_list_item(&mut self, step: i32) {
+        if let Some(index) = self.selected_play_list_index() {
+            let target = index as i32 + step;
+            if target >= 0 && (target as usize) < self.play_music_list.len() {
+                self.save_play_list();
+                self.play_music_list.swap(index, target as usize);
+                self.move_play_list_selection(step);
+            }
+        }
+    }
+
+    // Removing the playing music skips to the next one
+    pub fn remove_selected_from_play_list(&mut self) {
+        match self.selected_play_list_index() {
+            Some(index) => {
+                self.save_play_list();
                 self.play_music_list.remove(index);
+                self.move_play_list_selection(0);
+            }
+            None if self.playing_music.is_some() => {
+                self.play_next_music();
+                self.play_list_selection = 0;
             }
+            None => {}
+        }
+    }
+
+    // Moves the selected music to the front, to play after the playing one
+    pub fn play_selected_next(&mut self) {
+        if let Some(index) = self.selected_play_list_index() {
+            self.save_play_list();
+            let music = self.play_music_list.remove(index);
+            self.play_music_list.insert(0, music);
+            self.play_list_selection = self.playing_music.is_some() as usize;
+        }
+    }
+
+    pub fn play_selected_now(&mut self) {
+        if let Some(index) = self.selected_play_list_index() {
+            self.play_now(index);
         }
     }
 
+    // Skips to the music at `index` of the play list
+    pub fn play_now(&mut self, index: usize) {
+        let music = self.play_music_list.remove(index);
+        self.play_music_list.insert(0, music);
+        self.play_next_music();
+        self.play_list_selection = 0;
+    }
+
     pub fn clear_play_music_list(&mut self) {
         if self.play_music_list.len() > 0 {
+            self.save_play_list();
             self.play_music_list = Vec::new();
         }
     }
 
-    pub fn execute_search(&mut self) {
-        let mut astrict = self.get_search_string();
-        if astrict.len() > 0 {
-            astrict.remove(0);
+    fn save_play_list(&mut self) {
+        self.play_list_history.save(self.play_music_list.clone());
+    }
+
+    pub fn undo_play_list(&mut self) {
+        let current = self.play_music_list.clone();
+        match self.play_list_history.undo(current) {
+            Some(play_list) => self.play_music_list = play_list,
+            None => self.error = Some(String::from("Nothing to undo")),
+        }
+        self.move_play_list_selection(0);
+    }
+
+    pub fn redo_play_list(&mut self) {
+        let current = self.play_music_list.clone();
+        match self.play_list_history.redo(current) {
+            Some(play_list) => self.play_music_list = play_list,
+            None => self.error = Some(String::from("Nothing to redo")),
+        }
+        self.move_play_list_selection(0);
+    }
+
+    // Reading every tag of a large library takes a while, it is done without blocking the
+    // interface, a scan already running is left behind
+    pub fn rescan_library(&mut self) {
+        self.file_filter.forget_folders();
+        let (sender, receiver) = mpsc::channel();
+        let music_database = self.music_database.clone();
+        let filter = self.file_filter.clone();
+        thread::spawn(move || {
+            let _ = sender.send(Library::scan(&music_database, &filter));
+        });
+        self.scanning = Some(receiver);
+        self.info = Some(String::from("Reading the library..."));
+    }
+
+    // Whether the library is read, if not `library_use` runs again once it is
+    fn library_ready(&mut self, library_use: LibraryUse) -> bool {
+        if self.library.is_some() {
+            return true;
+        }
+        self.waiting_for_library.push(library_use);
+        if self.scanning.is_none() {
+            self.rescan_library();
+        }
+        false
+    }
+
+    fn poll_scanning(&mut self) {
+        let result = match self.scanning.as_ref().map(Receiver::try_recv) {
+            Some(Ok(result)) => result,
+            Some(Err(mpsc::TryRecvError::Empty)) | None => return,
+            Some(Err(mpsc::TryRecvError::Disconnected)) => {
+                self.scanning = None;
+                self.waiting_for_library.clear();
+                return;
+            }
+        };
+        self.scanning = None;
+        self.info = None;
+
+        match result {
+            Ok(library) => {
+                self.library = Some(library);
+                for library_use in std::mem::take(&mut self.waiting_for_library) {
+                    match library_use {
+                        LibraryUse::Smart(name) => self.load_smart_playlist(&name),
+                        LibraryUse::Dupes(use_fingerprint) => self.find_dupes(use_fingerprint),
+                    }
+                }
+            }
+            Err(err) => {
+                self.error = Some(err.to_string());
+                self.waiting_for_library.clear();
+            }
+        }
+    }
+
+    // Regenerate a smart playlist from the library index into the play list
+    pub fn load_smart_playlist(&mut self, name: &str) {
+        let query = match self.smart_playlists.get(name) {
+            Some(query) => match Query::parse(query) {
+                Ok(query) => query,
+                Err(err) => {
+                    self.error = Some(format!("{}: {}", name, err));
+                    return;
+                }
+            },
+            None => {
+                self.error = Some(format!("No smart playlist named '{}'", name));
+                return;
+            }
+        };
+
+        if !self.library_ready(LibraryUse::Smart(name.to_string())) {
+            return;
+        }
+        if let Some(library) = &self.library {
+            let tracks = library
+                .tracks
+                .iter()
+                .map(|music| Track::new(music, self.stats.get(&music.path)))
+                .collect();
+            let play_list = query.select(tracks, now_timestamp());
+            self.save_play_list();
+            self.play_music_list = play_list;
+            if self.play_music_list.is_empty() {
+                self.error = Some(format!("Smart playlist '{}' matched nothing", name));
+            }
+        }
+    }
+
+    pub fn open_tag_editor(&mut self) {
+        let paths = self.get_selected_paths();
+        if paths.is_empty() {
+            self.error = Some(String::from("No music selected"));
+        } else {
+            self.tag_editor = Some(TagEditor::new(paths));
+            self.set_mode(Mode::TagEdit);
+        }
+    }
+
+    pub fn close_tag_editor(&mut self) {
+        self.tag_editor = None;
+        self.set_mode(Mode::Browse);
+    }
+
+    pub fn save_tag_editor(&mut self) {
+        if let Some(tag_editor) = &self.tag_editor {
+            match tag_editor.changes() {
+                Ok(changes) => {
+                    let paths = tag_editor.paths.clone();
+                    self.close_tag_editor();
+                    self.write_tags(&paths, &changes);
+                    self.clear_marks();
+                }
+                Err(err) => self.error = Some(err),
+            }
+        }
+    }
+
+    pub fn write_tags(&mut self, paths: &[String], changes: &TagChanges) {
+        let mut failed = 0;
+        for path in paths {
+            if let Err(err) = write_audio_tags(path, changes) {
+                failed += 1;
+                self.error = Some(format!("{}: {}", path, err));
+            }
+        }
+        if failed > 1 {
+            self.error = Some(format!("Failed to write tags of {} files", failed));
+        }
+
+        self.refresh_music(paths);
+    }
+
+    // Re-read the tags of every loaded copy of the given files
+    pub fn refresh_music(&mut self, paths: &[String]) {
+        self.sorter.forget(paths);
+        let reload = |music: &mut Music| {
+            if paths.contains(&music.path) {
+                if let Ok(new_music) = Music::new(&music.path) {
+                    *music = Music {
+                        play_position: music.play_position,
+                        start_time: music.start_time,
+                        play_recorded: music.play_recorded,
+                        ..new_music
+                    };
+                }
+            }
+        };
+
+        self.play_music_list.iter_mut().for_each(reload);
+        self.playing_music.iter_mut().for_each(reload);
+        if let Some(library) = &mut self.library {
+            library.tracks.iter_mut().for_each(reload);
+        }
+    }
+
+    pub fn toggle_help(&mut self) {
+        if self.mode == Mode::Help {
+            self.set_mode(Mode::Browse);
+        } else {
+            self.help_scroll = 0;
+            self.set_mode(Mode::Help);
+        }
+    }
+
+    pub fn scroll_help(&mut self, step: i32) {
+        let lines = self.keymap.help().len() + commands::help().len();
+        let scroll = (self.help_scroll as i32 + step).max(0) as usize;
+        self.help_scroll = scroll.min(lines.saturating_sub(1));
+    }
+
+    // Dry run, the moves are only shown until they are confirmed
+    pub fn preview_organize(&mut self, pattern: &str) {
+        match organize::plan(&self.music_database, pattern, &self.file_filter) {
+            Ok(moves) if moves.is_empty() => {
+                self.error = Some(String::from("Everything is already organized"))
+            }
+            Ok(moves) => {
+                self.organize_preview = Some(Preview {
+                    pattern: pattern.to_string(),
+                    moves,
+                    scroll: 0,
+                });
+                self.set_mode(Mode::Organize);
+            }
+            Err(err) => self.error = Some(err),
+        }
+    }
+
+    pub fn scroll_organize_preview(&mut self, step: i32) {
+        if let Some(preview) = &mut self.organize_preview {
+            let scroll = (preview.scroll as i32 + step).max(0) as usize;
+            preview.scroll = scroll.min(preview.moves.len().saturating_sub(1));
+        }
+    }
+
+    pub fn cancel_organize(&mut self) {
+        self.organize_preview = None;
+        self.set_mode(Mode::Browse);
+    }
+
+    pub fn apply_organize(&mut self) {
+        if let Some(preview) = self.organize_preview.take() {
+            self.set_mode(Mode::Browse);
+            let (done, errors) = organize::apply(&preview.moves, &self.music_database);
+            if let Err(err) = organize::record(done.clone()) {
+                self.error = Some(err);
+            }
+            self.files_moved(&done, errors);
+        }
+    }
+
+    pub fn undo_organize(&mut self) {
+        match organize::undo(&self.music_database) {
+            Ok((done, errors)) => self.files_moved(&done, errors),
+            Err(err) => self.error = Some(err),
+        }
+    }
+
+    // Point everything that refers to a moved file to its new path
+    fn files_moved(&mut self, moves: &[Move], errors: Vec<String>) {
+        self.file_filter.forget_folders();
+        let renamed: Vec<(String, String)> = moves
+            .iter()
+            .map(|m| (m.from.clone(), m.to.clone()))
+            .collect();
+        let rename = |music: &mut Music| {
+            if let Some((_, to)) = renamed.iter().find(|(from, _)| *from == music.path) {
+                music.path = to.clone();
+            }
+        };
+        self.play_music_list.iter_mut().for_each(rename);
+        self.play_list_history
+            .states_mut()
+            .for_each(|play_list| play_list.iter_mut().for_each(rename));
+        self.playing_music.iter_mut().for_each(rename);
+        self.library = None;
+        if let Err(err) = self.stats.rename(&renamed) {
+            self.error = Some(err);
+        }
+
+        if !self.current_directory.exists() {
+            self.current_directory = PathBuf::from(&self.music_database);
+        }
+        if let Err(err) = self.populate_files() {
+            self.error = Some(err.to_string());
+        }
+
+        match errors.len() {
+            0 => {}
+            1 => self.error = Some(errors[0].clone()),
+            n => self.error = Some(format!("{} files were not moved: {}", n, errors[0])),
+        }
+    }
+
+    pub fn find_dupes(&mut self, use_fingerprint: bool) {
+        if !self.library_ready(LibraryUse::Dupes(use_fingerprint)) {
+            return;
+        }
+        if let Some(library) = &self.library {
+            let (sender, receiver) = mpsc::channel();
+            let tracks = library.tracks.clone();
+            thread::spawn(move || {
+                let _ = sender.send(dupes::find_duplicates(&tracks, use_fingerprint));
+            });
+            self.finding_dupes = Some(receiver);
+            self.info = Some(String::from("Looking for duplicates..."));
+        }
+    }
+
+    fn poll_finding_dupes(&mut self) {
+        let report = match self.finding_dupes.as_ref().map(Receiver::try_recv) {
+            Some(Ok(report)) => report,
+            Some(Err(mpsc::TryRecvError::Empty)) | None => return,
+            Some(Err(mpsc::TryRecvError::Disconnected)) => {
+                self.finding_dupes = None;
+                return;
+            }
+        };
+        self.finding_dupes = None;
+        self.info = None;
+
+        if report.groups.is_empty() {
+            self.error = Some(String::from("No duplicates found"));
+        } else {
+            self.dupes_report = Some(report);
+            self.selection_index = None;
+            if let Err(err) = self.populate_files() {
+                self.error = Some(err.to_string());
+            }
+        }
+    }
+
+    pub fn close_dupes(&mut self) {
+        self.dupes_report = None;
+        if let Err(err) = self.populate_files() {
+            self.error = Some(err.to_string());
+        }
+    }
+
+    fn selected_dupe(&mut self) -> Option<String> {
+        if self.dupes_report.is_none() {
+            self.error = Some(String::from("Run dupes first"));
+            return None;
+        }
+        match self.get_selected_directory_item() {
+            Some(DirectoryItem::File(path)) => Some(path),
+            _ => None,
+        }
+    }
+
+    // Keep the selected file and delete the other copies in its group, once confirmed
+    pub fn keep_dupe(&mut self) {
+        if let Some(path) = self.selected_dupe() {
+            let others: Vec<String> =
+                match self.dupes_report.as_ref().and_then(|r| r.group_of(&path)) {
+                    Some(group) => group
+                        .iter()
+                        .map(|duplicate| duplicate.path.clone())
+                        .filter(|other| *other != path)
+                        .collect(),
+                    None => return,
+                };
+            self.preview_delete(others);
+        }
+    }
+
+    // Delete the marked files, or the selected one, once confirmed, folders are left alone
+    pub fn delete_selected_files(&mut self) {
+        let paths: Vec<String> = self
+            .get_selected_directory_items()
+            .into_iter()
+            .filter_map(|item| match item {
+                DirectoryItem::File(path) => Some(path),
+                DirectoryItem::Directory(_) => None,
+            })
+            .collect();
+        if paths.is_empty() {
+            self.error = Some(String::from("No file selected"));
+            return;
+        }
+
+        self.preview_delete(paths);
+    }
+
+    // Nothing is deleted until the list is confirmed
+    fn preview_delete(&mut self, paths: Vec<String>) {
+        self.delete_preview = Some(DeletePreview { paths, scroll: 0 });
+        self.set_mode(Mode::Delete);
+    }
+
+    pub fn scroll_delete_preview(&mut self, step: i32) {
+        if let Some(preview) = &mut self.delete_preview {
+            let scroll = (preview.scroll as i32 + step).max(0) as usize;
+            preview.scroll = scroll.min(preview.paths.len().saturating_sub(1));
+        }
+    }
+
+    pub fn cancel_delete(&mut self) {
+        self.delete_preview = None;
+        self.set_mode(Mode::Browse);
+    }
+
+    pub fn apply_delete(&mut self) {
+        if let Some(preview) = self.delete_preview.take() {
+            self.set_mode(Mode::Browse);
+            self.delete_files(&preview.paths);
+            self.clear_marks();
+        }
+    }
+
+    fn delete_files(&mut self, paths: &[String]) {
+        let mut deleted: Vec<String> = Vec::new();
+        for path in paths {
+            match fs::remove_file(path) {
+                Ok(_) => deleted.push(path.clone()),
+                Err(err) => self.error = Some(format!("{}: {}", path, err)),
+            }
+        }
+        self.file_filter.forget_folders();
+
+        self.play_music_list
+            .retain(|music| !deleted.contains(&music.path));
+        self.play_list_history
+            .states_mut()
+            .for_each(|play_list| play_list.retain(|music| !deleted.contains(&music.path)));
+        if let Some(library) = &mut self.library {
+            library
+                .tracks
+                .retain(|music| !deleted.contains(&music.path));
+        }
+        if let Some(report) = &mut self.dupes_report {
+            report.remove(&deleted);
+            if report.groups.is_empty() {
+                self.dupes_report = None;
+            }
         }
+        if let Err(err) = self.stats.remove(&deleted) {
+            self.error = Some(err);
+        }
+
+        // Stopped without counting it as skipped, the next song starts on the next tick
+        if let Some(music) = &self.playing_music {
+            if deleted.contains(&music.path) {
+                self.playing_music = None;
+                self.new_sink().unwrap();
+            }
+        }
+
+        if let Err(err) = self.populate_files() {
+            self.error = Some(err.to_string());
+        }
+    }
+
+    pub fn execute_search(&mut self) {
+        let astrict = self.get_search_string();
         self.mode = Mode::Browse;
+        self.dupes_report = None;
         match self.populate_search_file(&astrict) {
             Ok(_) => {}
             Err(err) => self.error = Some(err.to_string()),
@@ -415,13 +1613,84 @@ impl<'a> App<'a> {
     }
 
     pub fn execute_command(&mut self) {
-        let command_string = self.get_command_strign();
-        self.command_buffer = Vec::new();
-        process_command(self, command_string);
+        let (command_string, error) = self.command_line.take();
         self.set_mode(Mode::Browse);
+        process_command(self, command_string);
         match self.populate_files() {
             Ok(_) => {}
             Err(err) => self.error = Some(err.to_string()),
         }
+        if error.is_some() {
+            self.error = error;
+        }
+    }
+
+    pub fn complete_command(&mut self, step: i32) {
+        let candidates = if self.command_line.is_completing() {
+            None
+        } else {
+            Some(commands::complete(
+                self,
+                &self.command_line.text_before_cursor(),
+            ))
+        };
+        self.command_line.complete(step, candidates);
+    }
+
+    // Opens a folder given from the current one or from the root
+    // Only below the music database, whose path is kept as configured so that going back up
+    // stops there
+    pub fn change_directory(&mut self, path: &str) {
+        let directory = match fs::canonicalize(self.current_directory.join(expand_home(path))) {
+            Ok(directory) if directory.is_dir() => directory,
+            _ => {
+                self.error = Some(format!("{}: not a folder", path));
+                return;
+            }
+        };
+        let root = fs::canonicalize(&self.music_database).unwrap_or_default();
+        let directory = match directory.strip_prefix(&root) {
+            Ok(relative) => PathBuf::from(&self.music_database).join(relative),
+            Err(_) => {
+                self.error = Some(format!("{}: not in the music database", path));
+                return;
+            }
+        };
+        let previous_dir = std::mem::replace(&mut self.current_directory, directory);
+        if let Err(err) = self.populate_files() {
+            self.current_directory = previous_dir;
+            self.error = Some(err.to_string());
+        } else if self.max_file_selection == 0 {
+            self.selection_index = None;
+        } else {
+            self.selection_index = Some(0);
+        }
+    }
+
+    // Plays through another output device, carrying on where the music was
+    pub fn set_output_device(&mut self, name: &str) {
+        let device = cpal::default_host()
+            .output_devices()
+            .ok()
+            .and_then(|mut devices| devices.find(|device| device.name().is_ok_and(|n| n == name)));
+        let device = match device {
+            Some(device) => device,
+            None => {
+                self.error = Some(format!("No output device named '{}'", name));
+                return;
+            }
+        };
+
+        match OutputStream::try_from_device(&device) {
+            Ok((stream, stream_handle)) => {
+                self.output_stream = Some(stream);
+                self.stream_handle = stream_handle;
+                match &self.playing_music {
+                    Some(music) => self.seek(music.play_position),
+                    None => self.new_sink().unwrap(),
+                }
+            }
+            Err(err) => self.error = Some(err.to_string()),
+        }
     }
 }
@@ -0,0 +1,307 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::config_dir;
+
+const HISTORY_FILE: &str = "command_history";
+// Older commands are forgotten
+const HISTORY_SIZE: usize = 500;
+
+// Candidates for the word being completed, Tab goes through them
+struct Completion {
+    // Where the word starts
+    start: usize,
+    candidates: Vec<String>,
+    index: usize,
+}
+
+// The text of the command mode, without the `:`, with a cursor and a history
+pub struct CommandLine {
+    text: Vec<char>,
+    cursor: usize,
+    history: Vec<String>,
+    // Position in the history while going through it, and the text typed before that
+    history_index: Option<usize>,
+    draft: Vec<char>,
+    completion: Option<Completion>,
+}
+
+fn history_path() -> Option<PathBuf> {
+    config_dir().map(|mut path| {
+        path.push(HISTORY_FILE);
+        path
+    })
+}
+
+impl CommandLine {
+    pub fn new() -> CommandLine {
+        let history = match history_path().and_then(|path| fs::read_to_string(path).ok()) {
+            Some(content) => content.lines().map(String::from).collect(),
+            None => Vec::new(),
+        };
+
+        CommandLine {
+            text: Vec::new(),
+            cursor: 0,
+            history,
+            history_index: None,
+            draft: Vec::new(),
+            completion: None,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.text.iter().collect()
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    // Everything before the cursor, what completion looks at
+    pub fn text_before_cursor(&self) -> String {
+        self.text[..self.cursor].iter().collect()
+    }
+
+    fn edited(&mut self) {
+        self.completion = None;
+        self.history_index = None;
+    }
+
+    pub fn insert(&mut self, chr: char) {
+        self.text.insert(self.cursor, chr);
+        self.cursor += 1;
+        self.edited();
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.text.remove(self.cursor);
+        }
+        self.edited();
+    }
+
+    pub fn delete(&mut self) {
+        if self.cursor < self.text.len() {
+            self.text.remove(self.cursor);
+        }
+        self.edited();
+    }
+
+    pub fn move_cursor(&mut self, step: i32) {
+        let cursor = self.cursor as i32 + step;
+        self.cursor = cursor.clamp(0, self.text.len() as i32) as usize;
+        self.completion = None;
+    }
+
+    pub fn move_cursor_home(&mut self) {
+        self.cursor = 0;
+        self.completion = None;
+    }
+
+    pub fn move_cursor_end(&mut self) {
+        self.cursor = self.text.len();
+        self.completion = None;
+    }
+
+    fn set_text(&mut self, text: Vec<char>) {
+        self.text = text;
+        self.cursor = self.text.len();
+        self.completion = None;
+    }
+
+    // Up goes to older commands, down back to newer ones and then to what was typed
+    pub fn history_previous(&mut self) {
+        let index = match self.history_index {
+            Some(0) => return,
+            Some(index) => index - 1,
+            None if self.history.is_empty() => return,
+            None => {
+                self.draft = self.text.clone();
+                self.history.len() - 1
+            }
+        };
+        self.history_index = Some(index);
+        self.set_text(self.history[index].chars().collect());
+    }
+
+    pub fn history_next(&mut self) {
+        match self.history_index {
+            Some(index) if index + 1 < self.history.len() => {
+                self.history_index = Some(index + 1);
+                self.set_text(self.history[index + 1].chars().collect());
+            }
+            Some(_) => {
+                self.history_index = None;
+                self.set_text(self.draft.clone());
+            }
+            None => {}
+        }
+    }
+
+    pub fn is_completing(&self) -> bool {
+        self.completion.is_some()
+    }
+
+    // Replaces the word before the cursor with the next candidate, `candidates` are where
+    // the word starts and what it could be, only needed when not completing yet
+    pub fn complete(&mut self, step: i32, candidates: Option<(usize, Vec<String>)>) {
+        if let Some(completion) = &mut self.completion {
+            let count = completion.candidates.len() as i32;
+            completion.index = (completion.index as i32 + step).rem_euclid(count) as usize;
+        } else {
+            let (start, candidates) = match candidates {
+                Some((start, candidates)) if !candidates.is_empty() => (start, candidates),
+                _ => return,
+            };
+            // Tab starts from the first candidate, Shift-Tab from the last
+            let index = if step > 0 { 0 } else { candidates.len() - 1 };
+            self.completion = Some(Completion {
+                start,
+                candidates,
+                index,
+            });
+        }
+
+        if let Some(completion) = &self.completion {
+            let candidate = &completion.candidates[completion.index];
+            let start = completion.start.min(self.cursor);
+            self.text.splice(start..self.cursor, candidate.chars());
+            self.cursor = start + candidate.chars().count();
+        }
+    }
+
+    // Empties the line and returns its text, which goes to the end of the history,
+    // and the error of writing the history
+    pub fn take(&mut self) -> (String, Option<String>) {
+        let text = self.text();
+        self.clear();
+
+        if !self.remember(text.trim()) {
+            return (text, None);
+        }
+        let error = history_path().and_then(|path| {
+            let mut content = self.history.join("\n");
+            content.push('\n');
+            fs::write(path, content).err().map(|err| err.to_string())
+        });
+
+        (text, error)
+    }
+
+    // Whether the command was added, blank lines and repeats are not
+    fn remember(&mut self, command: &str) -> bool {
+        if command.is_empty() || self.history.last().is_some_and(|last| last == command) {
+            return false;
+        }
+        self.history.push(command.to_string());
+        let excess = self.history.len().saturating_sub(HISTORY_SIZE);
+        self.history.drain(..excess);
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.set_text(Vec::new());
+        self.history_index = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Without reading the history file
+    fn command_line(history: &[&str]) -> CommandLine {
+        CommandLine {
+            text: Vec::new(),
+            cursor: 0,
+            history: history.iter().map(|command| command.to_string()).collect(),
+            history_index: None,
+            draft: Vec::new(),
+            completion: None,
+        }
+    }
+
+    fn type_text(line: &mut CommandLine, text: &str) {
+        text.chars().for_each(|chr| line.insert(chr));
+    }
+
+    fn candidates(start: usize, names: &[&str]) -> Option<(usize, Vec<String>)> {
+        Some((start, names.iter().map(|name| name.to_string()).collect()))
+    }
+
+    #[test]
+    fn editing_happens_at_the_cursor() {
+        let mut line = command_line(&[]);
+        type_text(&mut line, "ab");
+        line.move_cursor(-1);
+        line.insert('x');
+        assert_eq!((line.text(), line.cursor()), (String::from("axb"), 2));
+
+        line.backspace();
+        line.delete();
+        assert_eq!((line.text(), line.cursor()), (String::from("a"), 1));
+
+        line.move_cursor(-5);
+        assert_eq!(line.cursor(), 0);
+        line.backspace();
+        assert_eq!(line.text(), "a");
+        line.move_cursor(5);
+        assert_eq!(line.cursor(), 1);
+        line.move_cursor_home();
+        assert_eq!(line.text_before_cursor(), "");
+        line.move_cursor_end();
+        assert_eq!(line.text_before_cursor(), "a");
+    }
+
+    #[test]
+    fn history_keeps_the_latest_commands() {
+        let mut line = command_line(&[]);
+        assert!(!line.remember(""));
+        for i in 0..HISTORY_SIZE + 2 {
+            assert!(line.remember(&i.to_string()));
+        }
+        assert!(!line.remember(&(HISTORY_SIZE + 1).to_string()));
+        assert_eq!(line.history.len(), HISTORY_SIZE);
+        assert_eq!(line.history[0], "2");
+    }
+
+    #[test]
+    fn up_and_down_go_through_the_history_and_back_to_the_draft() {
+        let mut line = command_line(&["one", "two"]);
+        type_text(&mut line, "dr");
+        line.history_previous();
+        assert_eq!(line.text(), "two");
+        line.history_previous();
+        line.history_previous();
+        assert_eq!((line.text(), line.cursor()), (String::from("one"), 3));
+        line.history_next();
+        assert_eq!(line.text(), "two");
+        line.history_next();
+        assert_eq!(line.text(), "dr");
+        line.history_next();
+        assert_eq!(line.text(), "dr");
+    }
+
+    #[test]
+    fn tab_and_shift_tab_cycle_through_the_candidates() {
+        let mut line = command_line(&[]);
+        type_text(&mut line, "rm s");
+        line.complete(1, candidates(3, &["sort", "sh", "smart"]));
+        assert_eq!(line.text(), "rm sort");
+        line.complete(1, None);
+        assert_eq!(line.text(), "rm sh");
+        line.complete(-1, None);
+        line.complete(-1, None);
+        assert_eq!((line.text(), line.cursor()), (String::from("rm smart"), 8));
+
+        // Typing ends the completion, Shift-Tab starts from the last candidate
+        line.insert(' ');
+        assert!(!line.is_completing());
+        line.complete(-1, candidates(9, &["a", "b"]));
+        assert_eq!(line.text(), "rm smart b");
+        line.complete(1, None);
+        assert_eq!(line.text(), "rm smart a");
+    }
+}
@@ -1,43 +1,581 @@
-use crate::app::{App, PlayStyle};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
 
-pub fn process_command(app: &mut App, command_string: String) {
-    //split command buffer
-    let mut splist_command: Vec<String> = command_string
-        .trim_start_matches(":")
-        .split_ascii_whitespace()
-        .map(String::from)
-        .collect();
+use crate::app::{self, App, Insert, PlayStyle};
+use crate::file_ops::DirectoryItem;
+use crate::sort;
+use crate::tag_editor::parse_assignments;
+use crate::utils::split_path::expand_home;
+
+// A command of the command line, the first name is the one completed
+struct Command {
+    names: &'static [&'static str],
+    usage: &'static str,
+    help: &'static str,
+}
 
-    if splist_command.len() == 0 {
-        splist_command.append(&mut vec![String::new()]);
+#[rustfmt::skip]
+const COMMANDS: &[Command] = &[
+    Command { names: &["all"], usage: "all [-r]", help: "Add the songs of the current folder, -r: and of every folder below" },
+    Command { names: &["cd"], usage: "cd <path>", help: "Open a folder of the music database, relative to the current one or absolute" },
+    Command { names: &["clear", "cls"], usage: "clear", help: "Remove all songs from the playlist" },
+    Command { names: &["delete"], usage: "delete", help: "Delete the marked files, or the selected file, <Enter> confirms" },
+    Command { names: &["device"], usage: "device [name]", help: "List the output devices, or play through the named one" },
+    Command { names: &["dupes"], usage: "dupes [-f]", help: "List likely duplicate songs, -f: also compare how they sound" },
+    Command { names: &["finished"], usage: "finished", help: "Mark the selection as finished, forgetting where it was left" },
+    Command { names: &["goto"], usage: "goto <pos>", help: "Play the song at that position of the playlist now" },
+    Command { names: &["help"], usage: "help [command]", help: "List the commands, or explain one" },
+    Command { names: &["insert"], usage: "insert <pos>", help: "Add the selection before the song at that position of the playlist" },
+    Command { names: &["keep"], usage: "keep", help: "Keep the selected duplicate and delete the other files of its group, <Enter> confirms" },
+    Command { names: &["lyrics"], usage: "lyrics [offset <+ms|-ms|ms>]", help: "Show or hide the lyrics, or shift them by milliseconds" },
+    Command { names: &["next", "n"], usage: "next", help: "Play the next song in the playlist" },
+    Command { names: &["order", "od"], usage: "order", help: "Play the whole playlist" },
+    Command { names: &["organize"], usage: "organize <pattern|undo>", help: "Preview moving every song to a path built from its tags, or move them back" },
+    Command { names: &["rate"], usage: "rate <0-5>", help: "Rate the playing song, or the selected file" },
+    Command { names: &["redo"], usage: "redo", help: "Redo the last undone change to the playlist" },
+    Command { names: &["remove", "rm"], usage: "rm <pos|from-to>...", help: "Remove the songs at those positions of the playlist" },
+    Command { names: &["rescan"], usage: "rescan", help: "Re-read the music database used by smart playlists" },
+    Command { names: &["seek"], usage: "seek <time|+time|-time>", help: "Jump to a time of the playing song, or by that much, as s, m:ss or h:mm:ss" },
+    Command { names: &["shuffle", "sh"], usage: "shuffle", help: "Shuffle the playlist" },
+    Command { names: &["singlecycle", "sc"], usage: "singlecycle", help: "Put the playing song on repeat" },
+    Command { names: &["sort"], usage: "sort [folders|name|modified|size|track|duration]", help: "List the sort orders of the browser, or pick one" },
+    Command { names: &["smart"], usage: "smart [name]", help: "Fill the playlist from a smart playlist, or list them" },
+    Command { names: &["tag"], usage: "tag [field=value...]", help: "Open the tag editor, or set artist, title, album, track or genre of the selection" },
+    Command { names: &["undo"], usage: "undo", help: "Undo the last change to the playlist" },
+    Command { names: &["volume"], usage: "volume [0-125|+n|-n]", help: "Show the volume, or set it in percent" },
+];
+
+// The usage followed by the other names, like `clear | cls`
+fn syntax(command: &Command) -> String {
+    let mut syntax = command.usage.to_string();
+    let shown = command.usage.split(' ').next();
+    for name in command.names {
+        if shown != Some(*name) {
+            syntax.push_str(&format!(" | {}", name));
+        }
     }
-    match splist_command[0].to_ascii_uppercase().as_ref() {
-        "REMOVE" | "RM" => remove_command(app, splist_command),
-        "CLEAR" | "CLS" => app.clear_play_music_list(),
-        "ALL" => app.add_all_music_to_list(),
-        "ORDER" | "OD" => app.set_play_style(PlayStyle::PlayOrder),
-        "SINGLECYCLE" | "SC" => app.set_play_style(PlayStyle::SingleCycle),
-        "NEXT" | "N" => app.play_next_music(),
-        "SHUFFLE" | "SH" => app.shuffle_playlist(),
-        _ => app.error = Some(String::from("Not a command")),
+
+    syntax
+}
+
+// Syntax and description of every command, for the help
+pub fn help() -> Vec<(String, &'static str)> {
+    COMMANDS
+        .iter()
+        .map(|command| (syntax(command), command.help))
+        .collect()
+}
+
+fn find_command(name: &str) -> Option<&'static Command> {
+    let name = name.to_ascii_lowercase();
+    COMMANDS
+        .iter()
+        .find(|command| command.names.contains(&name.as_ref()))
+}
+
+// The words of every command separated by `;`, empty ones left out
+fn split_commands(command_string: &str) -> Vec<Vec<String>> {
+    command_string
+        .split(';')
+        .map(|line| {
+            line.trim()
+                .trim_start_matches(':')
+                .split_ascii_whitespace()
+                .map(String::from)
+                .collect::<Vec<String>>()
+        })
+        .filter(|words| !words.is_empty())
+        .collect()
+}
+
+// Runs the commands separated by `;` in turn, stopping at the first one that fails
+pub fn process_command(app: &mut App, command_string: String) {
+    for words in split_commands(&command_string) {
+        let command = match find_command(&words[0]) {
+            Some(command) => command,
+            None => {
+                app.error = Some(format!("Not a command: {}", words[0]));
+                break;
+            }
+        };
+        if let Err(err) = run_command(app, command.names[0], &words[1..]) {
+            app.error = Some(format!("{} (usage: {})", err, command.usage));
+            break;
+        }
     }
 
     app.move_select_up(1);
 }
 
-fn remove_command(app: &mut App, splist_command: Vec<String>) {
-    let mut remove_index: Vec<usize> = Vec::new();
-    for index_string in &splist_command[1..] {
-        match index_string.parse::<usize>() {
-            Ok(index) => {
-                if index > 0 {
-                    remove_index.push(index - 1);
-                }
+fn run_command(app: &mut App, name: &str, args: &[String]) -> Result<(), String> {
+    match name {
+        "all" => all_command(app, args)?,
+        "cd" if args.is_empty() => return Err(String::from("A folder is needed")),
+        "cd" => app.change_directory(&args.join(" ")),
+        "device" => device_command(app, args),
+        "dupes" => dupes_command(app, args)?,
+        "goto" => goto_command(app, args)?,
+        "help" => help_command(app, args)?,
+        "insert" => insert_command(app, args)?,
+        "lyrics" => lyrics_command(app, args)?,
+        "organize" if args.is_empty() => return Err(String::from("A pattern is needed")),
+        "organize" => organize_command(app, args),
+        "rate" => rate_command(app, args)?,
+        "remove" => remove_command(app, args)?,
+        "seek" => seek_command(app, args)?,
+        "smart" => smart_command(app, args),
+        "sort" => sort_command(app, args)?,
+        "tag" => tag_command(app, args)?,
+        "volume" => volume_command(app, args)?,
+        _ if !args.is_empty() => return Err(format!("{} takes no arguments", name)),
+        "clear" => app.clear_play_music_list(),
+        "delete" => app.delete_selected_files(),
+        "finished" => app.mark_finished(),
+        "keep" => app.keep_dupe(),
+        "next" => app.play_next_music(),
+        "order" => app.set_play_style(PlayStyle::PlayOrder),
+        "redo" => app.redo_play_list(),
+        "rescan" => app.rescan_library(),
+        "shuffle" => app.shuffle_playlist(),
+        "singlecycle" => app.set_play_style(PlayStyle::SingleCycle),
+        "undo" => app.undo_play_list(),
+        _ => unreachable!("{} is in COMMANDS but not run", name),
+    }
+
+    Ok(())
+}
+
+// A 1-based position of the playlist, as the play list numbers them
+fn parse_position(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(position) if position > 0 => Ok(position),
+        _ => Err(format!(
+            "{} is not a position, it must be a positive integer",
+            value
+        )),
+    }
+}
+
+// Positions and ranges of positions like `3-7`, as indices of a playlist of `len` songs
+fn parse_positions(args: &[String], len: usize) -> Result<Vec<usize>, String> {
+    let mut indices = Vec::new();
+    for arg in args {
+        let (from, to) = match arg.split_once('-') {
+            Some((from, to)) => match (parse_position(from), parse_position(to)) {
+                (Ok(from), Ok(to)) if from <= to => (from, to),
+                _ => return Err(format!("{} is not a range like 3-7", arg)),
+            },
+            None => {
+                let position = parse_position(arg)?;
+                (position, position)
             }
-            Err(_) => app.error = Some(String::from("It must be a positive integer")),
         };
+        // Checked before a range as long as `1-999999999` is built
+        if to > len {
+            return Err(format!("There is no song {}, the playlist has {}", to, len));
+        }
+        indices.extend(from - 1..to);
     }
-    if remove_index.len() > 0 {
-        app.remove_play_list_by_id(remove_index);
+
+    Ok(indices)
+}
+
+// Seconds, m:ss or h:mm:ss
+fn parse_time(value: &str) -> Result<Duration, String> {
+    let mut seconds: u64 = 0;
+    for part in value.split(':') {
+        let total = part
+            .parse::<u64>()
+            .ok()
+            .and_then(|part| seconds.checked_mul(60)?.checked_add(part));
+        match total {
+            Some(total) => seconds = total,
+            None => return Err(format!("{} is not a time", value)),
+        }
+    }
+
+    Ok(Duration::from_secs(seconds))
+}
+
+fn remove_command(app: &mut App, args: &[String]) -> Result<(), String> {
+    if args.is_empty() {
+        return Err(String::from("A position is needed"));
+    }
+    let indices = parse_positions(args, app.play_music_list.len())?;
+    app.remove_play_list_by_id(indices);
+
+    Ok(())
+}
+
+fn goto_command(app: &mut App, args: &[String]) -> Result<(), String> {
+    let position = match args {
+        [position] => parse_position(position)?,
+        _ => return Err(String::from("One position is needed")),
+    };
+    if position > app.play_music_list.len() {
+        return Err(format!(
+            "There is no song {}, the playlist has {}",
+            position,
+            app.play_music_list.len()
+        ));
+    }
+    app.play_now(position - 1);
+
+    Ok(())
+}
+
+// An absolute time, or one relative to the play position with `+` or `-`
+fn seek_command(app: &mut App, args: &[String]) -> Result<(), String> {
+    let value = match args {
+        [value] => value.as_str(),
+        _ => return Err(String::from("One time is needed")),
+    };
+    let play_position = match &app.playing_music {
+        Some(music) => music.play_position,
+        None => return Err(String::from("Nothing is playing")),
+    };
+    let position = if let Some(value) = value.strip_prefix('+') {
+        play_position.saturating_add(parse_time(value)?)
+    } else if let Some(value) = value.strip_prefix('-') {
+        play_position.saturating_sub(parse_time(value)?)
+    } else {
+        parse_time(value)?
+    };
+    app.seek(position);
+
+    Ok(())
+}
+
+// Percent, from 0 to 125 as the keys allow, or by that much with `+` or `-`
+fn volume_command(app: &mut App, args: &[String]) -> Result<(), String> {
+    let current = (app.player.volume() * 100.0).round() as i32;
+    let value = match args {
+        [] => {
+            app.info = Some(format!("Volume: {}%", current));
+            return Ok(());
+        }
+        [value] => value,
+        _ => return Err(String::from("One volume is needed")),
+    };
+    let volume = match value.parse::<i32>() {
+        Ok(change) if value.starts_with('+') || value.starts_with('-') => current + change,
+        Ok(volume) => volume,
+        Err(_) => return Err(format!("{} is not a volume", value)),
+    };
+    let volume = volume.clamp(0, 125) as f32 / 100.0;
+    app.update_volume(&|_| volume);
+
+    Ok(())
+}
+
+fn help_command(app: &mut App, args: &[String]) -> Result<(), String> {
+    match args {
+        [] => {
+            let names: Vec<&str> = COMMANDS.iter().map(|command| command.names[0]).collect();
+            app.info = Some(format!("Commands: {}", names.join(", ")));
+        }
+        [name] => match find_command(name) {
+            Some(command) => app.info = Some(format!("{}: {}", syntax(command), command.help)),
+            None => return Err(format!("Not a command: {}", name)),
+        },
+        _ => return Err(String::from("One command at a time")),
+    }
+
+    Ok(())
+}
+
+fn sort_command(app: &mut App, args: &[String]) -> Result<(), String> {
+    match args {
+        [] => {
+            app.info = Some(format!(
+                "Sorted by {}, orders: {}",
+                sort::order_name(app.sorter.order),
+                sort::order_names().join(", ")
+            ))
+        }
+        [name] => match sort::parse_order(name) {
+            Some(order) => app.sorter.order = order,
+            None => return Err(format!("{} is not a sort order", name)),
+        },
+        _ => return Err(String::from("One order is needed")),
+    }
+
+    Ok(())
+}
+
+fn smart_command(app: &mut App, args: &[String]) {
+    let name = args.join(" ");
+    if name.is_empty() {
+        let names: Vec<&str> = app.smart_playlists.keys().map(|s| s.as_str()).collect();
+        app.info = Some(format!("Smart playlists: {}", names.join(", ")));
+    } else {
+        app.load_smart_playlist(&name);
+    }
+}
+
+fn rate_command(app: &mut App, args: &[String]) -> Result<(), String> {
+    match args {
+        [rating] => match rating.parse::<u8>() {
+            Ok(rating) if rating <= 5 => app.rate_music(rating),
+            _ => return Err(String::from("The rating must be between 0 and 5")),
+        },
+        _ => return Err(String::from("One rating is needed")),
+    }
+
+    Ok(())
+}
+
+fn tag_command(app: &mut App, args: &[String]) -> Result<(), String> {
+    if args.is_empty() {
+        app.open_tag_editor();
+        return Ok(());
+    }
+
+    let changes = parse_assignments(&args.join(" "))?;
+    let paths = app.get_selected_paths();
+    app.write_tags(&paths, &changes);
+    app.clear_marks();
+
+    Ok(())
+}
+
+fn organize_command(app: &mut App, args: &[String]) {
+    let pattern = args.join(" ");
+    if pattern.eq_ignore_ascii_case("undo") {
+        app.undo_organize();
+    } else {
+        app.preview_organize(&pattern);
+    }
+}
+
+fn dupes_command(app: &mut App, args: &[String]) -> Result<(), String> {
+    match args {
+        [] => app.find_dupes(false),
+        [flag] if flag == "-f" => app.find_dupes(true),
+        _ => return Err(String::from("The only option is -f")),
+    }
+
+    Ok(())
+}
+
+fn lyrics_command(app: &mut App, args: &[String]) -> Result<(), String> {
+    let action = args.first().map(|s| s.to_ascii_lowercase());
+    match action.as_deref() {
+        None => app.lyrics_panel.toggle(),
+        Some("offset") => {
+            // +ms and -ms shift the lyrics, a bare number sets the offset
+            let value = args.get(1).map(|s| s.as_str()).unwrap_or("");
+            match value.parse::<i64>() {
+                Ok(ms) if value.starts_with('+') || value.starts_with('-') => {
+                    app.lyrics_panel.offset += ms
+                }
+                Ok(ms) => app.lyrics_panel.offset = ms,
+                Err(_) => return Err(String::from("The offset must be milliseconds")),
+            }
+        }
+        Some(action) => return Err(format!("{} is not an action", action)),
+    }
+
+    Ok(())
+}
+
+fn all_command(app: &mut App, args: &[String]) -> Result<(), String> {
+    match args {
+        [] => app.add_all_music_to_list(),
+        [flag] if flag == "-r" => {
+            let directory = app.current_directory.to_string_lossy().to_string();
+            app.enqueue_in_background(vec![DirectoryItem::Directory(directory)], Insert::End);
+        }
+        _ => return Err(String::from("The only option is -r")),
+    }
+
+    Ok(())
+}
+
+// Puts the selection before the song numbered `pos` in the playlist, as `rm` numbers them
+fn insert_command(app: &mut App, args: &[String]) -> Result<(), String> {
+    let position = match args {
+        [position] => parse_position(position)?,
+        _ => return Err(String::from("One position is needed")),
+    };
+    // One past the last song adds at the end
+    let len = app.play_music_list.len();
+    if position > len + 1 {
+        return Err(format!(
+            "There is no song {}, the playlist has {}",
+            position, len
+        ));
+    }
+    app.add_music_to_list(Insert::At(position - 1));
+
+    Ok(())
+}
+
+fn device_command(app: &mut App, args: &[String]) {
+    let name = args.join(" ");
+    if name.is_empty() {
+        app.info = Some(format!(
+            "Devices: {}",
+            app::output_device_names().join(", ")
+        ));
+    } else {
+        app.set_output_device(&name);
+    }
+}
+
+fn command_names() -> Vec<String> {
+    COMMANDS
+        .iter()
+        .map(|command| command.names[0].to_string())
+        .collect()
+}
+
+// Where the word before the cursor starts, in characters, and what it could be: a command,
+// a smart playlist, an output device or a path
+pub fn complete(app: &App, line: &str) -> (usize, Vec<String>) {
+    let (command, argument) = match line.split_once(' ') {
+        Some((command, argument)) => (command, argument),
+        None => {
+            let candidates = command_names()
+                .into_iter()
+                .filter(|name| name.starts_with(&line.to_ascii_lowercase()))
+                .map(|name| format!("{} ", name))
+                .collect();
+            return (0, candidates);
+        }
+    };
+    let argument_start = command.chars().count() + 1;
+    let starting_with = |names: Vec<String>| -> Vec<String> {
+        names
+            .into_iter()
+            .filter(|name| name.starts_with(argument))
+            .collect()
+    };
+
+    match command.to_ascii_uppercase().as_ref() {
+        "SMART" => (
+            argument_start,
+            starting_with(app.smart_playlists.keys().cloned().collect()),
+        ),
+        "DEVICE" => (argument_start, starting_with(app::output_device_names())),
+        "HELP" => (argument_start, starting_with(command_names())),
+        "SORT" => (argument_start, starting_with(sort::order_names())),
+        _ => {
+            let word_start = line.rfind(' ').map_or(0, |index| index + 1);
+            let word = &line[word_start..];
+            (
+                line[..word_start].chars().count(),
+                complete_path(&app.current_directory, word),
+            )
+        }
+    }
+}
+
+// Files and folders whose path starts with `word`, folders end with `/`
+fn complete_path(current_directory: &Path, word: &str) -> Vec<String> {
+    let (folder, prefix) = match word.rfind('/') {
+        Some(index) => word.split_at(index + 1),
+        None => ("", word),
+    };
+    let entries = match fs::read_dir(current_directory.join(expand_home(folder))) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut candidates: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Hidden files only when asked for
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let suffix = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", folder, name, suffix))
+        })
+        .collect();
+    candidates.sort();
+
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn commands_are_found_by_any_name_in_any_case() {
+        assert_eq!(
+            find_command("rm").map(|command| command.names[0]),
+            Some("remove")
+        );
+        assert_eq!(
+            find_command("CLS").map(|command| command.names[0]),
+            Some("clear")
+        );
+        assert!(find_command("nothing").is_none());
+    }
+
+    #[test]
+    fn syntax_lists_the_other_names() {
+        assert_eq!(syntax(find_command("clear").unwrap()), "clear | cls");
+        assert_eq!(
+            syntax(find_command("remove").unwrap()),
+            "rm <pos|from-to>... | remove"
+        );
+        assert_eq!(
+            syntax(find_command("seek").unwrap()),
+            "seek <time|+time|-time>"
+        );
+    }
+
+    #[test]
+    fn commands_are_split_on_semicolons() {
+        assert_eq!(
+            split_commands(" :clear ; ;all  -r;:next"),
+            vec![words(&["clear"]), words(&["all", "-r"]), words(&["next"])]
+        );
+        assert!(split_commands(" ; : ").is_empty());
+    }
+
+    #[test]
+    fn positions_start_at_one() {
+        assert_eq!(parse_position("3"), Ok(3));
+        assert!(parse_position("0").is_err());
+        assert!(parse_position("-1").is_err());
+        assert!(parse_position("x").is_err());
+    }
+
+    #[test]
+    fn ranges_become_indices() {
+        assert_eq!(
+            parse_positions(&words(&["2", "4-6"]), 10),
+            Ok(vec![1, 3, 4, 5])
+        );
+        assert_eq!(parse_positions(&words(&["5-5"]), 5), Ok(vec![4]));
+        assert!(parse_positions(&words(&["6-4"]), 10).is_err());
+        assert!(parse_positions(&words(&["4-"]), 10).is_err());
+        assert!(parse_positions(&words(&["0-3"]), 10).is_err());
+    }
+
+    #[test]
+    fn positions_past_the_playlist_are_refused() {
+        assert!(parse_positions(&words(&["11"]), 10).is_err());
+        assert!(parse_positions(&words(&["1-18446744073709551615"]), 10).is_err());
+    }
+
+    #[test]
+    fn times_are_seconds_minutes_or_hours() {
+        assert_eq!(parse_time("75"), Ok(Duration::from_secs(75)));
+        assert_eq!(parse_time("1:15"), Ok(Duration::from_secs(75)));
+        assert_eq!(parse_time("1:00:05"), Ok(Duration::from_secs(3605)));
+        assert!(parse_time("1:x").is_err());
+        assert!(parse_time("").is_err());
+        assert!(parse_time("99999999999999999:00:00").is_err());
     }
 }
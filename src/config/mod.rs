@@ -1,3 +1,6 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
 use exitfailure::ExitFailure;
 use serde::{Deserialize, Serialize};
 
@@ -5,6 +8,117 @@ use serde::{Deserialize, Serialize};
 pub struct InitConfig {
     pub music_database: String,
     pub theme: InitTheme,
+    // Smart playlist name -> query
+    #[serde(default)]
+    pub smart_playlists: BTreeMap<String, String>,
+    // Where to write the .scrobbler.log, disabled when missing
+    #[serde(default)]
+    pub scrobbler_log: Option<String>,
+    // Online scrobbling, disabled when missing
+    #[serde(default)]
+    pub scrobbler: Option<ScrobblerConfig>,
+    // How to show the cover art of the playing music
+    #[serde(default)]
+    pub cover_art: CoverArtMode,
+    // Clicks and the wheel in every pane
+    #[serde(default = "default_mouse")]
+    pub mouse: bool,
+    // Key sequence -> action of the browse mode or the dialogs, on top of the default keys
+    #[serde(default)]
+    pub keys: BTreeMap<String, String>,
+    // Continue the music of the last session where it was left instead of from its beginning
+    #[serde(default)]
+    pub resume: bool,
+    // Long files that continue where they were left, disabled when missing
+    #[serde(default)]
+    pub resume_files: Option<ResumeFilesConfig>,
+    // Order of the browser until changed with `sort`
+    #[serde(default)]
+    pub sort: SortOrder,
+    // Which files and folders the browser lists
+    #[serde(default)]
+    pub browser: BrowserConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BrowserConfig {
+    // Extensions of the files that can be played, every file is sniffed when empty
+    #[serde(default = "default_extensions")]
+    pub extensions: Vec<String>,
+    // Extensions never listed, even when sniffed as audio
+    #[serde(default)]
+    pub exclude_extensions: Vec<String>,
+    // Files and folders starting with a `.`, until toggled
+    #[serde(default)]
+    pub show_hidden: bool,
+    // Folders with nothing to play anywhere inside
+    #[serde(default)]
+    pub hide_empty_folders: bool,
+}
+
+impl Default for BrowserConfig {
+    fn default() -> BrowserConfig {
+        BrowserConfig {
+            extensions: default_extensions(),
+            exclude_extensions: Vec::new(),
+            show_hidden: false,
+            hide_empty_folders: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResumeFilesConfig {
+    // Files at least this many minutes long
+    #[serde(default)]
+    pub min_minutes: Option<u64>,
+    // Every file below these folders
+    #[serde(default)]
+    pub folders: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CoverArtMode {
+    // Kitty or sixel graphics when the terminal supports them, half blocks otherwise
+    #[default]
+    Auto,
+    Blocks,
+    Kitty,
+    Sixel,
+    Off,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    // Folders, then files, by name
+    #[default]
+    Folders,
+    // Folders and files mixed, by name
+    Name,
+    // The rest list folders first, then files newest, largest or longest first or by track
+    Modified,
+    Size,
+    Track,
+    Duration,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "service", rename_all = "lowercase")]
+pub enum ScrobblerConfig {
+    Lastfm {
+        #[serde(default)]
+        base_url: Option<String>,
+        api_key: String,
+        api_secret: String,
+        session_key: String,
+    },
+    Listenbrainz {
+        #[serde(default)]
+        base_url: Option<String>,
+        token: String,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -34,6 +148,8 @@ pub struct InitTheme {
     pub play_music_list_artist_color: String,
     pub play_music_list_album_color: String,
     pub play_music_list_header_color: String,
+    #[serde(default = "default_play_music_list_stats_color")]
+    pub play_music_list_stats_color: String,
     pub playing_music_border_color: String,
     pub playing_music_name_color: String,
     pub volume_icon_color: String,
@@ -41,15 +157,74 @@ pub struct InitTheme {
     pub gauge_color: String,
     pub gauge_border_color: String,
     pub gauge_label_color: String,
+    #[serde(default = "default_list_marked_color")]
+    pub list_marked_color: String,
+    #[serde(default = "default_lyrics_color")]
+    pub lyrics_color: String,
+    #[serde(default = "default_lyrics_current_color")]
+    pub lyrics_current_color: String,
+    #[serde(default = "default_spectrum_color")]
+    pub spectrum_color: String,
+    #[serde(default = "default_oscilloscope_color")]
+    pub oscilloscope_color: String,
+    #[serde(default = "default_waveform_unplayed_color")]
+    pub waveform_unplayed_color: String,
+}
+
+fn default_mouse() -> bool {
+    true
+}
+
+// What rodio decodes
+fn default_extensions() -> Vec<String> {
+    ["mp3", "flac", "ogg", "oga", "wav"]
+        .iter()
+        .map(|extension| extension.to_string())
+        .collect()
+}
+
+fn default_play_music_list_stats_color() -> String {
+    String::from("#ffb747")
+}
+
+fn default_list_marked_color() -> String {
+    String::from("#ffb747")
+}
+
+fn default_lyrics_color() -> String {
+    String::from("#9998af")
+}
+
+fn default_lyrics_current_color() -> String {
+    String::from("#d8ce2e")
+}
+
+fn default_spectrum_color() -> String {
+    String::from("#81a8fd")
+}
+
+fn default_oscilloscope_color() -> String {
+    String::from("#c3e88d")
+}
+
+fn default_waveform_unplayed_color() -> String {
+    String::from("#5c6370")
+}
+
+// Directory holding the configuration and the files the player keeps between runs
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home_path| {
+        let mut pathbuf = PathBuf::new();
+        pathbuf.push(home_path.to_str().unwrap());
+        pathbuf.push(".config");
+        pathbuf.push("music_player");
+        pathbuf
+    })
 }
 
 pub fn init() -> Result<InitConfig, ExitFailure> {
-    match dirs::home_dir() {
-        Some(home_path) => {
-            let mut pathbuf = std::path::PathBuf::new();
-            pathbuf.push(home_path.to_str().unwrap());
-            pathbuf.push(".config");
-            pathbuf.push("music_player");
+    match config_dir() {
+        Some(mut pathbuf) => {
             pathbuf.push("config.yml");
             let file = match std::fs::File::open(pathbuf) {
                 Ok(file) => file,
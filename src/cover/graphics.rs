@@ -0,0 +1,177 @@
+use std::env;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use crate::config::CoverArtMode;
+
+use super::Image;
+
+// Remove every image kitty shows
+pub const KITTY_CLEAR: &str = "\x1b_Ga=d,d=A,q=2\x1b\\";
+// Kitty takes base64 data in chunks of at most this size
+const KITTY_CHUNK: usize = 4096;
+// Assumed when the terminal does not report its size in pixels
+const DEFAULT_CELL_SIZE: (usize, usize) = (10, 20);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    // Two pixels per cell with the upper half block and colors
+    Blocks,
+    Kitty,
+    Sixel,
+}
+
+pub fn detect(mode: CoverArtMode) -> Option<Protocol> {
+    match mode {
+        CoverArtMode::Auto => Some(detect_terminal()),
+        CoverArtMode::Blocks => Some(Protocol::Blocks),
+        CoverArtMode::Kitty => Some(Protocol::Kitty),
+        CoverArtMode::Sixel => Some(Protocol::Sixel),
+        CoverArtMode::Off => None,
+    }
+}
+
+// What the terminal advertises through its environment
+fn detect_terminal() -> Protocol {
+    let term = env::var("TERM").unwrap_or_default();
+    let program = env::var("TERM_PROGRAM").unwrap_or_default();
+
+    // Multiplexers do not pass the graphics through
+    if env::var_os("TMUX").is_some() || term.starts_with("screen") || term.starts_with("tmux") {
+        Protocol::Blocks
+    } else if term == "xterm-kitty"
+        || env::var_os("KITTY_WINDOW_ID").is_some()
+        || ["WezTerm", "ghostty"].contains(&program.as_str())
+    {
+        Protocol::Kitty
+    } else if term.contains("sixel")
+        || ["foot", "foot-extra", "mlterm", "contour", "yaft-256color"].contains(&term.as_str())
+        || program == "mintty"
+    {
+        Protocol::Sixel
+    } else {
+        Protocol::Blocks
+    }
+}
+
+// Width and height of a cell in pixels
+#[cfg(unix)]
+pub fn cell_size() -> (usize, usize) {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    if result != 0 || size.ws_col == 0 || size.ws_row == 0 || size.ws_xpixel == 0 {
+        return DEFAULT_CELL_SIZE;
+    }
+    (
+        size.ws_xpixel as usize / size.ws_col as usize,
+        size.ws_ypixel as usize / size.ws_row as usize,
+    )
+}
+
+#[cfg(not(unix))]
+pub fn cell_size() -> (usize, usize) {
+    DEFAULT_CELL_SIZE
+}
+
+// Transmit and show raw RGB data scaled to `columns` x `rows` cells, the cursor stays put
+pub fn kitty(image: &Image, columns: usize, rows: usize) -> String {
+    let data: Vec<u8> = image.pixels.iter().flatten().copied().collect();
+    let encoded = STANDARD.encode(&data);
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(KITTY_CHUNK).collect();
+
+    let mut sequence = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = (i + 1 < chunks.len()) as u8;
+        let chunk = String::from_utf8_lossy(chunk);
+        if i == 0 {
+            sequence.push_str(&format!(
+                "\x1b_Ga=T,f=24,s={},v={},c={},r={},C=1,q=2,m={};{}\x1b\\",
+                image.width, image.height, columns, rows, more, chunk
+            ));
+        } else {
+            sequence.push_str(&format!("\x1b_Gm={};{}\x1b\\", more, chunk));
+        }
+    }
+    sequence
+}
+
+// 6 x 6 x 6 color cube
+fn color_index(pixel: [u8; 3]) -> usize {
+    let level = |value: u8| (value as usize * 5 + 127) / 255;
+    level(pixel[0]) * 36 + level(pixel[1]) * 6 + level(pixel[2])
+}
+
+pub fn sixel(image: &Image) -> String {
+    let indexes: Vec<usize> = image.pixels.iter().map(|p| color_index(*p)).collect();
+    let mut used = [false; 216];
+    for index in &indexes {
+        used[*index] = true;
+    }
+
+    let mut sequence = format!("\x1bPq\"1;1;{};{}", image.width, image.height);
+    for (index, _) in used.iter().enumerate().filter(|(_, used)| **used) {
+        let percent = |level: usize| level * 100 / 5;
+        sequence.push_str(&format!(
+            "#{};2;{};{};{}",
+            index,
+            percent(index / 36),
+            percent(index / 6 % 6),
+            percent(index % 6)
+        ));
+    }
+
+    // Bands of six pixel rows, one pass per color
+    for band in (0..image.height).step_by(6) {
+        let rows = (image.height - band).min(6);
+        let mut in_band = [false; 216];
+        for y in band..band + rows {
+            for x in 0..image.width {
+                in_band[indexes[y * image.width + x]] = true;
+            }
+        }
+
+        let mut first = true;
+        for (color, _) in in_band.iter().enumerate().filter(|(_, used)| **used) {
+            if !first {
+                sequence.push('$');
+            }
+            first = false;
+            sequence.push_str(&format!("#{}", color));
+
+            let mut run: Option<(char, usize)> = None;
+            for x in 0..image.width {
+                let mut bits = 0;
+                for dy in 0..rows {
+                    if indexes[(band + dy) * image.width + x] == color {
+                        bits |= 1 << dy;
+                    }
+                }
+                let chr = (63 + bits) as u8 as char;
+                run = match run {
+                    Some((previous, count)) if previous == chr => Some((chr, count + 1)),
+                    Some((previous, count)) => {
+                        push_run(&mut sequence, previous, count);
+                        Some((chr, 1))
+                    }
+                    None => Some((chr, 1)),
+                };
+            }
+            if let Some((chr, count)) = run {
+                push_run(&mut sequence, chr, count);
+            }
+        }
+        sequence.push('-');
+    }
+    sequence.push_str("\x1b\\");
+
+    sequence
+}
+
+fn push_run(sequence: &mut String, chr: char, count: usize) {
+    if count > 3 {
+        sequence.push_str(&format!("!{}{}", count, chr));
+    } else {
+        (0..count).for_each(|_| sequence.push(chr));
+    }
+}
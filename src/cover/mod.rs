@@ -0,0 +1,378 @@
+use std::fs;
+use std::io::{self, Cursor, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crossterm::cursor::MoveTo;
+use crossterm::queue;
+use image::io::{Limits, Reader};
+use tui::layout::Rect;
+
+use crate::config::CoverArtMode;
+use crate::music::Music;
+
+use self::graphics::Protocol;
+
+pub mod graphics;
+
+// Looked for next to the music when it has no embedded art, in this order
+const FOLDER_IMAGES: &[&str] = &["cover", "folder", "front", "album"];
+const FOLDER_IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png"];
+// Decoded art is shrunk to this size, plenty for a terminal
+const MAX_SIZE: usize = 512;
+// Art larger than this is refused before it is decoded, a bad header must not exhaust memory
+const MAX_DECODE_SIZE: u32 = 10_000;
+const MAX_DECODE_MEMORY: u64 = 256 * 1024 * 1024;
+
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[u8; 3]>,
+}
+
+impl Image {
+    // JPEG or PNG, alpha is blended onto black
+    pub fn decode(data: &[u8]) -> Result<Image, String> {
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(MAX_DECODE_SIZE);
+        limits.max_image_height = Some(MAX_DECODE_SIZE);
+        limits.max_alloc = Some(MAX_DECODE_MEMORY);
+
+        let mut reader = Reader::new(Cursor::new(data))
+            .with_guessed_format()
+            .map_err(|err| err.to_string())?;
+        reader.limits(limits);
+        let decoded = reader.decode().map_err(|err| err.to_string())?.into_rgba8();
+
+        let blend = |value: u8, alpha: u8| (value as u16 * alpha as u16 / 255) as u8;
+        Ok(Image {
+            width: decoded.width() as usize,
+            height: decoded.height() as usize,
+            pixels: decoded
+                .pixels()
+                .map(|pixel| {
+                    let [r, g, b, a] = pixel.0;
+                    [blend(r, a), blend(g, a), blend(b, a)]
+                })
+                .collect(),
+        })
+    }
+
+    // The largest size with the same aspect ratio that fits
+    pub fn fit(&self, width: usize, height: usize) -> (usize, usize) {
+        let scale = (width as f32 / self.width as f32).min(height as f32 / self.height as f32);
+        (
+            ((self.width as f32 * scale).round() as usize).clamp(1, width.max(1)),
+            ((self.height as f32 * scale).round() as usize).clamp(1, height.max(1)),
+        )
+    }
+
+    // Every pixel is the average of the pixels it covers
+    pub fn resize(&self, width: usize, height: usize) -> Image {
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let top = y * self.height / height;
+            let bottom = ((y + 1) * self.height / height).max(top + 1);
+            for x in 0..width {
+                let left = x * self.width / width;
+                let right = ((x + 1) * self.width / width).max(left + 1);
+                let mut sum = [0usize; 3];
+                for sy in top..bottom {
+                    for sx in left..right {
+                        let pixel = self.pixels[sy * self.width + sx];
+                        for c in 0..3 {
+                            sum[c] += pixel[c] as usize;
+                        }
+                    }
+                }
+                let count = (bottom - top) * (right - left);
+                pixels.push([
+                    (sum[0] / count) as u8,
+                    (sum[1] / count) as u8,
+                    (sum[2] / count) as u8,
+                ]);
+            }
+        }
+
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+// APIC frame, FLAC PICTURE block or MP4 covr atom, the front cover if there are several
+fn embedded_art(path: &str) -> Option<Vec<u8>> {
+    let extension = Path::new(path)
+        .extension()?
+        .to_string_lossy()
+        .to_lowercase();
+
+    match extension.as_ref() {
+        "mp3" => {
+            let tag = id3::Tag::read_from_path(path).ok()?;
+            let pictures: Vec<&id3::frame::Picture> = tag.pictures().collect();
+            pictures
+                .iter()
+                .find(|p| p.picture_type == id3::frame::PictureType::CoverFront)
+                .or_else(|| pictures.first())
+                .map(|p| p.data.clone())
+        }
+        "flac" => {
+            let tag = metaflac::Tag::read_from_path(path).ok()?;
+            let pictures: Vec<&metaflac::block::Picture> = tag.pictures().collect();
+            pictures
+                .iter()
+                .find(|p| p.picture_type == metaflac::block::PictureType::CoverFront)
+                .or_else(|| pictures.first())
+                .map(|p| p.data.clone())
+        }
+        "m4a" | "m4b" | "m4p" | "m4v" | "mp4" => {
+            let tag = mp4ameta::Tag::read_from_path(path).ok()?;
+            match tag.artwork()? {
+                mp4ameta::Data::Jpeg(data) | mp4ameta::Data::Png(data) => Some(data.clone()),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// cover.jpg, folder.png and the like in the folder of the music
+fn folder_art(path: &str) -> Option<Vec<u8>> {
+    let mut images: Vec<(usize, std::path::PathBuf)> = Vec::new();
+    for entry in fs::read_dir(Path::new(path).parent()?).ok()?.flatten() {
+        let file = entry.path();
+        let stem = file.file_stem()?.to_string_lossy().to_lowercase();
+        let extension = match file.extension() {
+            Some(extension) => extension.to_string_lossy().to_lowercase(),
+            None => continue,
+        };
+        if let Some(rank) = FOLDER_IMAGES.iter().position(|name| *name == stem) {
+            if FOLDER_IMAGE_EXTENSIONS.contains(&extension.as_ref()) {
+                images.push((rank, file));
+            }
+        }
+    }
+    images.sort();
+
+    fs::read(&images.first()?.1).ok()
+}
+
+pub fn load(path: &str) -> Option<Image> {
+    let data = embedded_art(path).or_else(|| folder_art(path))?;
+    let image = Image::decode(&data).ok()?;
+    if image.width > MAX_SIZE || image.height > MAX_SIZE {
+        let (width, height) = image.fit(MAX_SIZE, MAX_SIZE);
+        Some(image.resize(width, height))
+    } else {
+        Some(image)
+    }
+}
+
+// Art of the playing music, decoded in a background thread when the music changes
+pub struct CoverArt {
+    pub protocol: Option<Protocol>,
+    music_path: Option<String>,
+    image: Option<Image>,
+    receiver: Option<Receiver<Option<Image>>>,
+    // What kitty or sixel graphics last put on the screen
+    shown: Option<(String, Rect)>,
+    clear_screen: bool,
+}
+
+impl CoverArt {
+    pub fn new(mode: CoverArtMode) -> CoverArt {
+        CoverArt {
+            protocol: graphics::detect(mode),
+            music_path: None,
+            image: None,
+            receiver: None,
+            shown: None,
+            clear_screen: false,
+        }
+    }
+
+    pub fn update(&mut self, playing_music: Option<&Music>) {
+        if self.protocol.is_none() {
+            return;
+        }
+
+        let music_path = playing_music.map(|music| music.path.clone());
+        if music_path != self.music_path {
+            self.image = None;
+            self.receiver = None;
+            if let Some(path) = &music_path {
+                let (sender, receiver) = mpsc::channel();
+                let path = path.clone();
+                thread::spawn(move || {
+                    let _ = sender.send(load(&path));
+                });
+                self.receiver = Some(receiver);
+            }
+            self.music_path = music_path;
+        }
+
+        if let Some(receiver) = &self.receiver {
+            if let Ok(image) = receiver.try_recv() {
+                self.image = image;
+                self.receiver = None;
+            }
+        }
+    }
+
+    pub fn image(&self) -> Option<&Image> {
+        self.image.as_ref()
+    }
+
+    // Sixel images can only be removed by drawing every cell again
+    pub fn take_clear_screen(&mut self) -> bool {
+        std::mem::take(&mut self.clear_screen)
+    }
+
+    // Kitty and sixel images go straight to the terminal once tui has drawn the frame,
+    // `area` is the empty space left for them
+    pub fn draw_graphics(&mut self, area: Option<Rect>, out: &mut impl Write) -> io::Result<()> {
+        let protocol = match self.protocol {
+            Some(Protocol::Kitty) => Protocol::Kitty,
+            Some(Protocol::Sixel) => Protocol::Sixel,
+            _ => return Ok(()),
+        };
+        let wanted = match (area, &self.image, &self.music_path) {
+            (Some(area), Some(_), Some(path)) => Some((path.clone(), area)),
+            _ => None,
+        };
+        if wanted == self.shown {
+            return Ok(());
+        }
+
+        if protocol == Protocol::Kitty {
+            out.write_all(graphics::KITTY_CLEAR.as_bytes())?;
+        } else if self.shown.is_some() {
+            self.shown = None;
+            self.clear_screen = true;
+            return Ok(());
+        }
+
+        if let (Some((_, area)), Some(image)) = (&wanted, &self.image) {
+            let (x, y, sequence) = match protocol {
+                Protocol::Kitty => {
+                    // Cells are about twice as high as wide
+                    let (width, height) = image.fit(area.width as usize, area.height as usize * 2);
+                    let rows = height.div_ceil(2);
+                    let x = area.x + (area.width - width as u16) / 2;
+                    let y = area.y + (area.height - rows as u16) / 2;
+                    (x, y, graphics::kitty(image, width, rows))
+                }
+                _ => {
+                    let (cell_width, cell_height) = graphics::cell_size();
+                    let (width, height) = image.fit(
+                        area.width as usize * cell_width,
+                        area.height as usize * cell_height,
+                    );
+                    let columns = width.div_ceil(cell_width);
+                    let rows = height.div_ceil(cell_height);
+                    let x = area.x + area.width.saturating_sub(columns as u16) / 2;
+                    let y = area.y + area.height.saturating_sub(rows as u16) / 2;
+                    (x, y, graphics::sixel(&image.resize(width, height)))
+                }
+            };
+            queue!(out, MoveTo(x, y))?;
+            out.write_all(sequence.as_bytes())?;
+        }
+        out.flush()?;
+        self.shown = wanted;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageOutputFormat, Rgba, RgbaImage};
+
+    use super::*;
+
+    fn encode(image: RgbaImage, format: ImageOutputFormat) -> Vec<u8> {
+        let mut data = Cursor::new(Vec::new());
+        match format {
+            ImageOutputFormat::Jpeg(_) => image::DynamicImage::ImageRgba8(image)
+                .into_rgb8()
+                .write_to(&mut data, format),
+            _ => image.write_to(&mut data, format),
+        }
+        .unwrap();
+        data.into_inner()
+    }
+
+    #[test]
+    fn png_alpha_is_blended_onto_black() {
+        let mut image = RgbaImage::new(2, 1);
+        image.put_pixel(0, 0, Rgba([200, 100, 50, 255]));
+        image.put_pixel(1, 0, Rgba([200, 100, 50, 0]));
+        let decoded = Image::decode(&encode(image, ImageOutputFormat::Png)).unwrap();
+
+        assert_eq!((decoded.width, decoded.height), (2, 1));
+        assert_eq!(decoded.pixels, [[200, 100, 50], [0, 0, 0]]);
+    }
+
+    #[test]
+    fn jpeg() {
+        let image = RgbaImage::from_pixel(16, 8, Rgba([255, 255, 255, 255]));
+        let decoded = Image::decode(&encode(image, ImageOutputFormat::Jpeg(90))).unwrap();
+
+        assert_eq!((decoded.width, decoded.height), (16, 8));
+        assert!(decoded.pixels.iter().flatten().all(|value| *value > 250));
+    }
+
+    #[test]
+    fn oversized_jpeg_header_is_refused() {
+        let image = RgbaImage::new(8, 8);
+        let mut data = encode(image, ImageOutputFormat::Jpeg(90));
+        // Height and width of the frame header follow its marker, length and precision
+        let frame = data
+            .windows(2)
+            .position(|bytes| bytes == [0xFF, 0xC0])
+            .unwrap();
+        data[frame + 5..frame + 9].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF]);
+
+        assert!(Image::decode(&data).is_err());
+    }
+
+    #[test]
+    fn corrupt_data() {
+        let image = RgbaImage::from_pixel(32, 32, Rgba([1, 2, 3, 255]));
+        let png = encode(image.clone(), ImageOutputFormat::Png);
+        let jpeg = encode(image, ImageOutputFormat::Jpeg(90));
+
+        assert!(Image::decode(&png[..png.len() / 2]).is_err());
+        assert!(Image::decode(&jpeg[..20]).is_err());
+        assert!(Image::decode(b"").is_err());
+        assert!(Image::decode(b"not an image at all").is_err());
+    }
+
+    #[test]
+    fn fit_and_resize() {
+        let image = Image {
+            width: 4,
+            height: 2,
+            pixels: vec![
+                [0, 0, 0],
+                [100, 100, 100],
+                [10, 10, 10],
+                [10, 10, 10],
+                [0, 0, 0],
+                [100, 100, 100],
+                [30, 30, 30],
+                [30, 30, 30],
+            ],
+        };
+        assert_eq!(image.fit(2, 2), (2, 1));
+        assert_eq!(image.fit(100, 1), (2, 1));
+
+        let resized = image.resize(2, 1);
+        assert_eq!(resized.pixels, [[50, 50, 50], [20, 20, 20]]);
+    }
+}
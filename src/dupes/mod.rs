@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use rodio::Source;
+
+use crate::file_ops::get_audio_source;
+use crate::music::Music;
+
+// Copies of the same recording may differ this much in length
+const DURATION_TOLERANCE: Duration = Duration::from_secs(2);
+// How much audio the fingerprint covers
+const FINGERPRINT_DURATION: Duration = Duration::from_secs(30);
+// One loudness value per window
+const FINGERPRINT_WINDOW: Duration = Duration::from_millis(100);
+// Correlation above which two fingerprints are the same recording
+const FINGERPRINT_THRESHOLD: f32 = 0.95;
+
+// Lowercase letters and digits only, so "The Song (Live)" and "the song - live" match
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn tag_key(music: &Music) -> (String, String) {
+    let title = if music.title.trim().is_empty() {
+        &music.name
+    } else {
+        &music.title
+    };
+    (normalize(&music.artist), normalize(title))
+}
+
+fn close_duration(a: &Music, b: &Music) -> bool {
+    let (longer, shorter) = if a.total_duration > b.total_duration {
+        (a.total_duration, b.total_duration)
+    } else {
+        (b.total_duration, a.total_duration)
+    };
+    longer - shorter <= DURATION_TOLERANCE
+}
+
+fn find(parents: &mut Vec<usize>, i: usize) -> usize {
+    if parents[i] != i {
+        let root = find(parents, parents[i]);
+        parents[i] = root;
+    }
+    parents[i]
+}
+
+fn union(parents: &mut Vec<usize>, a: usize, b: usize) {
+    let (a, b) = (find(parents, a), find(parents, b));
+    if a != b {
+        parents[b] = a;
+    }
+}
+
+// Loudness envelope of the start of the decoded audio, normalized to zero mean and unit variance
+pub fn fingerprint(path: &str) -> Option<Vec<f32>> {
+    let source = get_audio_source(path).ok()?;
+    let channels = source.channels().max(1) as usize;
+    let window =
+        (source.sample_rate() as f32 * FINGERPRINT_WINDOW.as_secs_f32()) as usize * channels;
+    let windows = (FINGERPRINT_DURATION.as_millis() / FINGERPRINT_WINDOW.as_millis()) as usize;
+
+    let mut envelope: Vec<f32> = Vec::with_capacity(windows);
+    let mut sum = 0.0;
+    let mut count = 0;
+    for sample in source.convert_samples::<f32>() {
+        sum += sample * sample;
+        count += 1;
+        if count == window {
+            envelope.push((sum / count as f32).sqrt());
+            sum = 0.0;
+            count = 0;
+            if envelope.len() == windows {
+                break;
+            }
+        }
+    }
+
+    let mean = envelope.iter().sum::<f32>() / envelope.len().max(1) as f32;
+    let variance = envelope
+        .iter()
+        .map(|v| (v - mean) * (v - mean))
+        .sum::<f32>()
+        / envelope.len().max(1) as f32;
+    // Silence can not be told apart
+    if variance <= f32::EPSILON {
+        return None;
+    }
+
+    let deviation = variance.sqrt();
+    Some(envelope.iter().map(|v| (v - mean) / deviation).collect())
+}
+
+fn similarity(a: &[f32], b: &[f32]) -> f32 {
+    let len = a.len().min(b.len());
+    if len == 0 {
+        return 0.0;
+    }
+    a.iter().zip(b).map(|(a, b)| a * b).sum::<f32>() / len as f32
+}
+
+pub struct Duplicate {
+    pub path: String,
+    pub size: u64,
+    pub duration: Duration,
+}
+
+impl Duplicate {
+    fn new(music: &Music) -> Duplicate {
+        Duplicate {
+            path: music.path.clone(),
+            size: fs::metadata(&music.path).map(|m| m.len()).unwrap_or(0),
+            duration: music.total_duration,
+        }
+    }
+
+    // `3  Artist/Album/Song.mp3  320kbps 7.4MB`
+    pub fn label(&self, group: usize, music_database: &str) -> String {
+        let path = Path::new(&self.path);
+        let relative = path.strip_prefix(music_database).unwrap_or(path);
+        let kbps = match self.duration.as_secs() {
+            0 => String::from("?"),
+            secs => (self.size * 8 / 1000 / secs).to_string(),
+        };
+        format!(
+            "{}  {}  {}kbps {:.1}MB",
+            group + 1,
+            relative.to_string_lossy(),
+            kbps,
+            self.size as f64 / 1_000_000.0
+        )
+    }
+}
+
+// Groups of likely duplicates shown in the browser pane
+pub struct Report {
+    pub groups: Vec<Vec<Duplicate>>,
+}
+
+impl Report {
+    pub fn paths(&self) -> Vec<String> {
+        self.groups
+            .iter()
+            .flatten()
+            .map(|duplicate| duplicate.path.clone())
+            .collect()
+    }
+
+    pub fn labels(&self, music_database: &str) -> Vec<String> {
+        let mut labels = Vec::new();
+        for (i, group) in self.groups.iter().enumerate() {
+            for duplicate in group {
+                labels.push(duplicate.label(i, music_database));
+            }
+        }
+        labels
+    }
+
+    pub fn group_of(&self, path: &str) -> Option<&Vec<Duplicate>> {
+        self.groups
+            .iter()
+            .find(|group| group.iter().any(|duplicate| duplicate.path == path))
+    }
+
+    // Forget deleted files, a group left with one file is no longer a duplicate
+    pub fn remove(&mut self, paths: &[String]) {
+        for group in &mut self.groups {
+            group.retain(|duplicate| !paths.contains(&duplicate.path));
+        }
+        self.groups.retain(|group| group.len() > 1);
+    }
+}
+
+// Group likely copies of the same song: same normalized artist and title with about the
+// same duration, and with `use_fingerprint` also tracks whose audio starts the same way
+pub fn find_duplicates(tracks: &[Music], use_fingerprint: bool) -> Report {
+    let mut parents: Vec<usize> = (0..tracks.len()).collect();
+
+    let mut by_tags: HashMap<(String, String), Vec<usize>> = HashMap::new();
+    for (i, music) in tracks.iter().enumerate() {
+        by_tags.entry(tag_key(music)).or_default().push(i);
+    }
+    for indexes in by_tags.values() {
+        for (n, &a) in indexes.iter().enumerate() {
+            for &b in &indexes[n + 1..] {
+                if close_duration(&tracks[a], &tracks[b]) {
+                    union(&mut parents, a, b);
+                }
+            }
+        }
+    }
+
+    if use_fingerprint {
+        // Only tracks of about the same length need to be compared
+        let mut by_duration: Vec<usize> = (0..tracks.len()).collect();
+        by_duration.sort_by_key(|&i| tracks[i].total_duration);
+        let mut fingerprints: HashMap<usize, Option<Vec<f32>>> = HashMap::new();
+
+        for (n, &a) in by_duration.iter().enumerate() {
+            for &b in &by_duration[n + 1..] {
+                if !close_duration(&tracks[a], &tracks[b]) {
+                    break;
+                }
+                if find(&mut parents, a) == find(&mut parents, b) {
+                    continue;
+                }
+                for i in [a, b] {
+                    fingerprints
+                        .entry(i)
+                        .or_insert_with(|| fingerprint(&tracks[i].path));
+                }
+                if let (Some(Some(fa)), Some(Some(fb))) =
+                    (fingerprints.get(&a), fingerprints.get(&b))
+                {
+                    if similarity(fa, fb) >= FINGERPRINT_THRESHOLD {
+                        union(&mut parents, a, b);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..tracks.len() {
+        let root = find(&mut parents, i);
+        groups.entry(root).or_default().push(i);
+    }
+    let mut groups: Vec<Vec<Duplicate>> = groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|group| group.iter().map(|&i| Duplicate::new(&tracks[i])).collect())
+        .collect();
+    for group in &mut groups {
+        group.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    groups.sort_by(|a, b| a[0].path.cmp(&b[0].path));
+
+    Report { groups }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn music(path: &str, artist: &str, title: &str, seconds: u64) -> Music {
+        Music {
+            path: String::from(path),
+            name: String::from(path),
+            artist: String::from(artist),
+            title: String::from(title),
+            album: String::new(),
+            album_artist: String::new(),
+            genre: String::new(),
+            year: None,
+            track_number: None,
+            disc_number: None,
+            play_position: Duration::from_secs(0),
+            total_duration: Duration::from_secs(seconds),
+            start_time: None,
+            play_recorded: false,
+        }
+    }
+
+    fn paths(report: &Report) -> Vec<Vec<&str>> {
+        report
+            .groups
+            .iter()
+            .map(|group| group.iter().map(|d| d.path.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn same_tags_and_length_are_grouped() {
+        let tracks = [
+            music("a", "The Band", "The Song (Live)", 200),
+            music("b", "the band", "the song - live", 201),
+            music("c", "The Band", "The Song (Live)", 210),
+            music("d", "Other", "The Song (Live)", 200),
+        ];
+        assert_eq!(paths(&find_duplicates(&tracks, false)), [["a", "b"]]);
+    }
+
+    #[test]
+    fn groups_are_joined_through_a_common_copy() {
+        // a and c are 4 seconds apart, b is close to both
+        let tracks = [
+            music("c", "Band", "Song", 204),
+            music("a", "Band", "Song", 200),
+            music("b", "Band", "Song", 202),
+        ];
+        assert_eq!(paths(&find_duplicates(&tracks, false)), [["a", "b", "c"]]);
+    }
+
+    #[test]
+    fn groups_left_with_one_file_are_removed() {
+        let tracks = [
+            music("a", "Band", "Song", 200),
+            music("b", "Band", "Song", 200),
+            music("c", "Band", "Other", 100),
+            music("d", "Band", "Other", 100),
+            music("e", "Band", "Other", 100),
+        ];
+        let mut report = find_duplicates(&tracks, false);
+        report.remove(&[String::from("a"), String::from("c")]);
+        assert_eq!(paths(&report), [["d", "e"]]);
+        assert!(report.group_of("b").is_none());
+    }
+
+    #[test]
+    fn similarity_of_fingerprints() {
+        let a = [1.0, -1.0, 1.0, -1.0];
+        let b = [-1.0, 1.0, -1.0, 1.0];
+        assert_eq!(similarity(&a, &a), 1.0);
+        assert_eq!(similarity(&a, &b), -1.0);
+        // Only the common length is compared
+        assert_eq!(similarity(&a[..2], &a), 1.0);
+        assert_eq!(similarity(&[], &a), 0.0);
+    }
+}
@@ -1,15 +1,17 @@
+use std::collections::{HashMap, HashSet};
 use std::fs::read_dir;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use audiotags::{AudioTag, Tag};
-use rodio::decoder::DecoderError;
 use rodio::Decoder;
 use rodio::Source;
 
 use crate::app::App;
+use crate::config::BrowserConfig;
+use crate::music::Music;
 use crate::utils::split_path::split_path_to_name;
 
 #[derive(Eq, PartialEq, PartialOrd, Ord, Debug, Clone)]
@@ -20,15 +22,28 @@ pub enum DirectoryItem {
     Directory(String),
 }
 
+impl DirectoryItem {
+    pub fn path(&self) -> &str {
+        match self {
+            DirectoryItem::File(path) | DirectoryItem::Directory(path) => path,
+        }
+    }
+}
+
 pub struct Audio {
     pub artist: String,
     pub title: String,
     pub album: String,
+    pub album_artist: String,
+    pub genre: String,
+    pub year: Option<i32>,
+    pub track_number: Option<u16>,
+    pub disc_number: Option<u16>,
     pub duration: Duration,
 }
 
 impl Audio {
-    fn new(tag: Box<dyn AudioTag>, duration: Duration) -> Audio {
+    fn new(tag: Box<dyn AudioTag>, genre: String, duration: Duration) -> Audio {
         let artist = match tag.artist() {
             Some(s) => s.to_string(),
             None => "".to_string(),
@@ -42,15 +57,136 @@ impl Audio {
             None => "".to_string(),
         };
 
+        let album_artist = match tag.album_artist() {
+            Some(s) => s.to_string(),
+            None => "".to_string(),
+        };
+
         Audio {
             artist,
             title,
             album,
+            album_artist,
+            genre,
+            year: tag.year(),
+            track_number: tag.track_number(),
+            disc_number: tag.disc_number(),
             duration,
         }
     }
 }
 
+// Which files can be played and which files and folders the browser lists
+#[derive(Clone)]
+pub struct FileFilter {
+    extensions: Vec<String>,
+    exclude_extensions: Vec<String>,
+    show_hidden: bool,
+    hide_empty_folders: bool,
+    // Whether a folder has something to play, by canonical path, until the files change
+    searched: HashMap<PathBuf, bool>,
+}
+
+impl FileFilter {
+    pub fn new(config: &BrowserConfig) -> FileFilter {
+        let lowercase = |extensions: &[String]| {
+            extensions
+                .iter()
+                .map(|extension| extension.trim_start_matches('.').to_lowercase())
+                .collect()
+        };
+
+        FileFilter {
+            extensions: lowercase(&config.extensions),
+            exclude_extensions: lowercase(&config.exclude_extensions),
+            show_hidden: config.show_hidden,
+            hide_empty_folders: config.hide_empty_folders,
+            searched: HashMap::new(),
+        }
+    }
+
+    pub fn toggle_hidden(&mut self) {
+        self.show_hidden = !self.show_hidden;
+        self.forget_folders();
+    }
+
+    // Search the folders again, after files were deleted or moved
+    pub fn forget_folders(&mut self) {
+        self.searched.clear();
+    }
+
+    // By the extension, or by the content when there is no extension or no list of them
+    pub fn is_playable(&self, path: &Path) -> Result<bool, io::Error> {
+        match path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+        {
+            Some(ext) if self.exclude_extensions.contains(&ext) => Ok(false),
+            Some(ext) if !self.extensions.is_empty() => Ok(self.extensions.contains(&ext)),
+            _ => check_audio_file(path),
+        }
+    }
+
+    fn is_listed(&mut self, path: &Path) -> Result<bool, io::Error> {
+        if is_hidden(path) && !self.show_hidden {
+            Ok(false)
+        } else if path.is_file() {
+            self.is_playable(path)
+        } else {
+            Ok(!self.hide_empty_folders || self.has_playable(path))
+        }
+    }
+
+    fn has_playable(&mut self, dir: &Path) -> bool {
+        let dir = match fs::canonicalize(dir) {
+            Ok(dir) => dir,
+            Err(_) => return false,
+        };
+        if let Some(found) = self.searched.get(&dir) {
+            return *found;
+        }
+
+        let found = self.search_playable(&dir, &mut HashSet::new());
+        self.searched.insert(dir, found);
+        found
+    }
+
+    // Stops at the first file that can be played, looking at the files before the sub folders.
+    // `visited` holds the canonical folders already searched, a symlink back up the tree is skipped
+    fn search_playable(&self, dir: &Path, visited: &mut HashSet<PathBuf>) -> bool {
+        if !fs::canonicalize(dir).is_ok_and(|dir| visited.insert(dir)) {
+            return false;
+        }
+        let entries = match read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return false,
+        };
+
+        let mut folders: Vec<PathBuf> = Vec::new();
+        for path in entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+        {
+            if !self.show_hidden && is_hidden(&path) {
+                continue;
+            }
+            if path.is_dir() {
+                folders.push(path);
+            } else if self.is_playable(&path).unwrap_or(false) {
+                return true;
+            }
+        }
+        folders
+            .iter()
+            .any(|folder| self.search_playable(folder, visited))
+    }
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
 pub fn get_files_for_current_directory(app: &mut App) -> Result<Vec<DirectoryItem>, io::Error> {
     //Get list, unwrap, and convert results to &Path
     let dir_items: Vec<PathBuf> = match read_dir(&app.current_directory) {
@@ -61,12 +197,13 @@ pub fn get_files_for_current_directory(app: &mut App) -> Result<Vec<DirectoryIte
     //Convert items to DirectoryItem
     let mut files: Vec<DirectoryItem> = Vec::new();
     for item in dir_items {
+        // Hidden, not playable or empty
+        if !app.file_filter.is_listed(&item)? {
+            continue;
+        }
         if item.is_file() {
-            // Check whether it is an audio file
-            if check_audio_file(&item)? {
-                let file = DirectoryItem::File(String::from(item.to_string_lossy()));
-                files.push(file);
-            }
+            let file = DirectoryItem::File(String::from(item.to_string_lossy()));
+            files.push(file);
         } else {
             let file = DirectoryItem::Directory(String::from(item.to_string_lossy()));
             files.push(file);
@@ -93,12 +230,12 @@ pub fn get_files_for_current_directory_astrict(
         if !split_path_to_name(&path_string).contains(astrict) {
             continue;
         };
+        if !app.file_filter.is_listed(&item)? {
+            continue;
+        }
         if item.is_file() {
-            // Check whether it is an audio file
-            if check_audio_file(&item)? {
-                let file = DirectoryItem::File(path_string);
-                files.push(file);
-            }
+            let file = DirectoryItem::File(path_string);
+            files.push(file);
         } else {
             let file = DirectoryItem::Directory(path_string);
             files.push(file);
@@ -108,11 +245,10 @@ pub fn get_files_for_current_directory_astrict(
     Ok(files)
 }
 
-pub fn check_audio_file(path: &PathBuf) -> Result<bool, io::Error> {
-    if let Some(t) = infer::get_from_path(path.to_str().unwrap())? {
-        let mime_type = t.mime_type();
-
-        return Ok(mime_type.contains("audio") || mime_type.contains("video"));
+// Sniffs the content, slow for large folders
+pub fn check_audio_file(path: &Path) -> Result<bool, io::Error> {
+    if let Some(t) = infer::get_from_path(path)? {
+        return Ok(t.mime_type().starts_with("audio"));
     }
 
     Ok(false)
@@ -141,10 +277,272 @@ pub fn read_audio_file<'a>(path: &str, extension: &str) -> Result<Audio, String>
         };
     };
 
-    Ok(Audio::new(tag, duration))
+    Ok(Audio::new(tag, read_genre(path), duration))
 }
 
-pub fn get_audio_source(path: &str) -> Result<Decoder<File>, DecoderError> {
-    let file = File::open(path).unwrap();
-    Decoder::new(file)
+// audiotags does not expose the genre, so read it from the underlying tag
+pub fn read_genre(path: &str) -> String {
+    let extension = match std::path::Path::new(path).extension() {
+        Some(ext) => ext.to_string_lossy().to_lowercase(),
+        None => return String::new(),
+    };
+
+    let genre = match extension.as_ref() {
+        "mp3" => id3::Tag::read_from_path(path)
+            .ok()
+            .and_then(|tag| tag.genre().map(String::from)),
+        "flac" => metaflac::Tag::read_from_path(path).ok().and_then(|tag| {
+            tag.get_vorbis("GENRE")
+                .and_then(|mut genres| genres.next().map(String::from))
+        }),
+        "m4a" | "m4b" | "m4p" | "m4v" | "mp4" => mp4ameta::Tag::read_from_path(path)
+            .ok()
+            .and_then(|tag| tag.genre().map(String::from)),
+        _ => None,
+    };
+
+    genre.unwrap_or_default()
+}
+
+// Tag values to write, `None` leaves the tag untouched
+#[derive(Default, Clone)]
+pub struct TagChanges {
+    pub artist: Option<String>,
+    pub title: Option<String>,
+    pub album: Option<String>,
+    // `Some(None)` removes the track number
+    pub track_number: Option<Option<u16>>,
+    pub genre: Option<String>,
+}
+
+pub fn write_audio_tags(path: &str, changes: &TagChanges) -> Result<(), String> {
+    let mut tag = match Tag::default().read_from_path(path) {
+        Ok(tag) => tag,
+        // An MP3 without a tag gets a new one
+        Err(audiotags::Error::Id3TagError(err)) if matches!(err.kind, id3::ErrorKind::NoTag) => {
+            Box::new(audiotags::Id3v2Tag::new())
+        }
+        Err(err) => return Err(err.to_string()),
+    };
+    if let Some(artist) = &changes.artist {
+        tag.set_artist(artist);
+    }
+    if let Some(title) = &changes.title {
+        tag.set_title(title);
+    }
+    if let Some(album) = &changes.album {
+        tag.set_album_title(album);
+    }
+    match changes.track_number {
+        Some(Some(track_number)) => tag.set_track_number(track_number),
+        Some(None) => tag.remove_track_number(),
+        None => {}
+    }
+    if let Err(err) = tag.write_to_path(path) {
+        return Err(err.to_string());
+    }
+
+    match &changes.genre {
+        Some(genre) => write_genre(path, genre),
+        None => Ok(()),
+    }
+}
+
+// audiotags can not write the genre either, see `read_genre`
+fn write_genre(path: &str, genre: &str) -> Result<(), String> {
+    let extension = match std::path::Path::new(path).extension() {
+        Some(ext) => ext.to_string_lossy().to_lowercase(),
+        None => return Err(String::from("Unsupported file type")),
+    };
+
+    match extension.as_ref() {
+        "mp3" => {
+            let mut tag = match id3::Tag::read_from_path(path) {
+                Ok(tag) => tag,
+                Err(err) if matches!(err.kind, id3::ErrorKind::NoTag) => id3::Tag::new(),
+                Err(err) => return Err(err.to_string()),
+            };
+            tag.set_genre(genre);
+            tag.write_to_path(path, id3::Version::Id3v24)
+                .map_err(|err| err.to_string())
+        }
+        "flac" => {
+            let mut tag = metaflac::Tag::read_from_path(path).map_err(|err| err.to_string())?;
+            tag.set_vorbis("GENRE", vec![genre]);
+            tag.save().map_err(|err| err.to_string())
+        }
+        "m4a" | "m4b" | "m4p" | "m4v" | "mp4" => {
+            let mut tag = mp4ameta::Tag::read_from_path(path).map_err(|err| err.to_string())?;
+            tag.set_genre(genre);
+            tag.write_to_path(path).map_err(|err| err.to_string())
+        }
+        _ => Err(String::from("Unsupported file type")),
+    }
+}
+
+// Collect every playable file below `dir`, descending into sub folders
+pub fn get_audio_files_recursive(
+    dir: &Path,
+    filter: &FileFilter,
+) -> Result<Vec<String>, io::Error> {
+    let mut files: Vec<String> = Vec::new();
+    collect_audio_files(dir, filter, &mut HashSet::new(), &mut files)?;
+
+    Ok(files)
+}
+
+// `visited` holds the canonical folders already read, a symlink back up the tree is skipped
+fn collect_audio_files(
+    dir: &Path,
+    filter: &FileFilter,
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<String>,
+) -> Result<(), io::Error> {
+    if !visited.insert(fs::canonicalize(dir)?) {
+        return Ok(());
+    }
+    let mut dir_items: Vec<PathBuf> = read_dir(dir)?
+        .filter_map(|f| f.ok())
+        .map(|f| f.path())
+        .collect();
+    dir_items.sort();
+
+    for item in dir_items {
+        if item.is_dir() {
+            collect_audio_files(&item, filter, visited, files)?;
+        } else if filter.is_playable(&item)? {
+            files.push(String::from(item.to_string_lossy()));
+        }
+    }
+
+    Ok(())
+}
+
+// The music of the files and everything below the folders, album by album: folder by folder,
+// then by disc and track number. Returns the last error of the files that could not be read
+pub fn read_music_recursive(
+    items: &[DirectoryItem],
+    filter: &FileFilter,
+) -> (Vec<Music>, Option<String>) {
+    let mut paths: Vec<String> = Vec::new();
+    let mut error = None;
+    for item in items {
+        match item {
+            DirectoryItem::File(path) => paths.push(path.clone()),
+            DirectoryItem::Directory(path) => {
+                match get_audio_files_recursive(Path::new(path), filter) {
+                    Ok(files) => paths.extend(files),
+                    Err(err) => error = Some(format!("{}: {}", path, err)),
+                }
+            }
+        }
+    }
+
+    let mut music_list: Vec<Music> = Vec::new();
+    for path in paths {
+        match Music::new(&path) {
+            Ok(music) => music_list.push(music),
+            Err(err) => error = Some(format!("{}: {}", path, err)),
+        }
+    }
+    music_list.sort_by(|a, b| {
+        let folder = |music: &Music| Path::new(&music.path).parent().map(Path::to_path_buf);
+        (folder(a), a.disc_number, a.track_number, &a.path).cmp(&(
+            folder(b),
+            b.disc_number,
+            b.track_number,
+            &b.path,
+        ))
+    });
+
+    (music_list, error)
+}
+
+// The file may be gone since it was listed, then the open error is returned
+pub fn get_audio_source(path: &str) -> Result<Decoder<File>, String> {
+    let file = File::open(path).map_err(|err| format!("{}: {}", path, err))?;
+    Decoder::new(file).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An empty folder of its own under the system temp folder
+    fn temp_dir(name: &str) -> PathBuf {
+        let name = format!("music_player_{}_{}", name, std::process::id());
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn recursive_listing_skips_symlink_loops() {
+        let dir = temp_dir("symlink_loop");
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.mp3"), b"").unwrap();
+        fs::write(dir.join("sub/b.mp3"), b"").unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("sub/loop")).unwrap();
+
+        let filter = FileFilter::new(&BrowserConfig::default());
+        let files = get_audio_files_recursive(&dir, &filter).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<&str> = files.iter().map(|path| split_path_to_name(path)).collect();
+        assert_eq!(names, ["a.mp3", "b.mp3"]);
+    }
+
+    #[test]
+    fn empty_folders_are_searched_through_symlink_loops_once() {
+        let dir = temp_dir("empty_loop");
+        fs::create_dir_all(dir.join("empty/sub")).unwrap();
+        fs::create_dir(dir.join("full")).unwrap();
+        fs::write(dir.join("full/a.mp3"), b"").unwrap();
+        std::os::unix::fs::symlink(dir.join("empty"), dir.join("empty/sub/loop")).unwrap();
+
+        let config = BrowserConfig {
+            hide_empty_folders: true,
+            ..BrowserConfig::default()
+        };
+        let mut filter = FileFilter::new(&config);
+        assert!(!filter.is_listed(&dir.join("empty")).unwrap());
+        assert!(filter.is_listed(&dir.join("full")).unwrap());
+
+        // Kept until the filter is told the files changed
+        fs::write(dir.join("empty/b.mp3"), b"").unwrap();
+        assert!(!filter.is_listed(&dir.join("empty")).unwrap());
+        filter.forget_folders();
+        assert!(filter.is_listed(&dir.join("empty")).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_files_are_an_error() {
+        let path = temp_dir("missing").join("gone.mp3");
+        let err = get_audio_source(&path.to_string_lossy()).err().unwrap();
+        assert!(err.starts_with(&*path.to_string_lossy()));
+    }
+
+    #[test]
+    fn tags_are_written_to_untagged_mp3() {
+        let dir = temp_dir("untagged");
+        let path = dir.join("song.mp3");
+        fs::write(&path, [0xFF, 0xFB, 0x90, 0x00]).unwrap();
+        let path = path.to_string_lossy().to_string();
+
+        let changes = TagChanges {
+            artist: Some(String::from("Artist")),
+            genre: Some(String::from("Jazz")),
+            ..TagChanges::default()
+        };
+        let result = write_audio_tags(&path, &changes);
+        let tag = id3::Tag::read_from_path(&path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_ok());
+        let tag = tag.unwrap();
+        assert_eq!(tag.artist(), Some("Artist"));
+        assert_eq!(tag.genre(), Some("Jazz"));
+    }
 }
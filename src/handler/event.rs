@@ -1,45 +1,71 @@
-use crossterm::event::{self, Event, KeyCode};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyModifiers, MouseButton, MouseEventKind};
 use exitfailure::ExitFailure;
 
-use crate::app::{self, App, Mode};
+use crate::app::{self, App, Focus, Insert, Mode};
+use crate::keymap::{Action, Key, Lookup};
+
+// How long a key sequence like `gg` may take
+const KEY_TIMEOUT: Duration = Duration::from_secs(1);
 
 pub fn handle_event(app: &mut App, music_database: &str) -> Result<bool, ExitFailure> {
-    let mut is_loop = true;
+    let is_loop = true;
+    let event = event::read().unwrap();
+
+    // Mouse moves would clear errors as soon as they are shown
+    if let Event::Mouse(mouse) = event {
+        if app.mode == Mode::Browse {
+            match mouse.kind {
+                MouseEventKind::Down(MouseButton::Left) => app.click(mouse.column, mouse.row),
+                MouseEventKind::ScrollDown => app.scroll(mouse.column, mouse.row, true),
+                MouseEventKind::ScrollUp => app.scroll(mouse.column, mouse.row, false),
+                _ => {}
+            }
+        }
+        return Ok(is_loop);
+    }
 
     if app.error != None {
         app.error = None;
     }
+    app.info = None;
 
     //Handle input
-    if let Event::Key(key) = event::read().unwrap() {
-        if app.mode == Mode::Browse {
-            match key.code {
-                KeyCode::Char('q') => is_loop = false,
-                KeyCode::Char('g') => app.move_select_top(),
-                KeyCode::Char('G') => app.move_select_bottom(),
-                KeyCode::Down => app.move_select_down(1),
-                KeyCode::Char('j') => app.move_select_down(1),
-                KeyCode::Char('J') => app.move_select_down(5),
-                KeyCode::Up => app.move_select_up(1),
-                KeyCode::Char('k') => app.move_select_up(1),
-                KeyCode::Char('K') => app.move_select_up(5),
-                KeyCode::Char('n') => app.next_page(),
-                KeyCode::Char('N') => app.previous_page(),
-                KeyCode::Char('l') => app.open_folder(),
-                KeyCode::Char('h') => app.back_previous_folder(music_database),
-                KeyCode::Enter => app.add_music_to_list(),
-                KeyCode::Char(' ') => app.stop_or_start_play(),
-                KeyCode::Char('-') => app.update_volume(&|v| if v > 0.0 {v - 0.05} else {0.0}),
-                KeyCode::Char('=') => app.update_volume(&|v| if v < 1.25 {v + 0.05} else {1.25}),
-                KeyCode::Char('+') => app.update_volume(&|v| if v < 1.25 {v + 0.05} else {1.25}),
-                KeyCode::Char('|') => app.set_mode(Mode::Search),
-                KeyCode::Char(':') => app.set_mode(Mode::Command),
-                KeyCode::Esc => {
-                    app.populate_files()?;
-                    app.search_buffer = Vec::new();
+    if let Event::Key(key) = event {
+        if app.mode == Mode::TagEdit {
+            let key = Key::from_event(key);
+            let action = app.keymap.lookup_dialog(key);
+            let typed = !key
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+            if let Some(tag_editor) = &mut app.tag_editor {
+                // Characters are typed, whatever they are bound to
+                match (key.code, action) {
+                    (KeyCode::Char(chr), _) if typed => tag_editor.push_char(chr),
+                    (KeyCode::Backspace, _) => tag_editor.pop_char(),
+                    (_, Some(Action::DialogDown)) => tag_editor.select_next(),
+                    (_, Some(Action::DialogUp)) => tag_editor.select_previous(),
+                    (_, Some(Action::Confirm)) => app.save_tag_editor(),
+                    (_, Some(Action::Cancel)) => app.close_tag_editor(),
+                    _ => {}
                 }
-                _ => {}
             }
+            return Ok(is_loop);
+        }
+
+        if app.mode == Mode::Organize || app.mode == Mode::Delete || app.mode == Mode::Help {
+            press_dialog_key(app, Key::from_event(key));
+            return Ok(is_loop);
+        }
+
+        if app.mode == Mode::Browse {
+            // <Esc> gives up a sequence that was started
+            if key.code == KeyCode::Esc && !app.pending_keys.is_empty() {
+                app.pending_keys.clear();
+                return Ok(is_loop);
+            }
+            return press_key(app, Key::from_event(key), music_database);
         }
 
         if app.mode == Mode::Search {
@@ -47,9 +73,7 @@ pub fn handle_event(app: &mut App, music_database: &str) -> Result<bool, ExitFai
                 KeyCode::Char(chr) => app.add_to_search_buffer(chr),
                 KeyCode::Enter => app.execute_search(),
                 KeyCode::Backspace => {
-                    if app.search_buffer.len() > 1 {
-                        app.search_buffer.truncate(app.search_buffer.len() - 1);
-                    };
+                    app.search_buffer.pop();
                 }
                 KeyCode::Esc => {
                     app.set_mode(app::Mode::Browse);
@@ -61,16 +85,21 @@ pub fn handle_event(app: &mut App, music_database: &str) -> Result<bool, ExitFai
 
         if app.mode == app::Mode::Command {
             match key.code {
-                KeyCode::Char(chr) => app.add_to_command_buffer(chr),
+                KeyCode::Char(chr) => app.command_line.insert(chr),
                 KeyCode::Enter => app.execute_command(),
-                KeyCode::Backspace => {
-                    if app.command_buffer.len() > 1 {
-                        app.command_buffer.truncate(app.command_buffer.len() - 1);
-                    };
-                }
+                KeyCode::Backspace => app.command_line.backspace(),
+                KeyCode::Delete => app.command_line.delete(),
+                KeyCode::Left => app.command_line.move_cursor(-1),
+                KeyCode::Right => app.command_line.move_cursor(1),
+                KeyCode::Home => app.command_line.move_cursor_home(),
+                KeyCode::End => app.command_line.move_cursor_end(),
+                KeyCode::Up => app.command_line.history_previous(),
+                KeyCode::Down => app.command_line.history_next(),
+                KeyCode::Tab => app.complete_command(1),
+                KeyCode::BackTab => app.complete_command(-1),
                 KeyCode::Esc => {
                     app.set_mode(app::Mode::Browse);
-                    app.command_buffer = Vec::new();
+                    app.command_line.clear();
                 }
                 _ => {}
             }
@@ -79,3 +108,176 @@ pub fn handle_event(app: &mut App, music_database: &str) -> Result<bool, ExitFai
 
     Ok(is_loop)
 }
+
+fn press_key(app: &mut App, key: Key, music_database: &str) -> Result<bool, ExitFailure> {
+    app.pending_keys.push(key);
+    app.pending_keys_time = Instant::now();
+
+    match app.keymap.lookup(&app.pending_keys) {
+        Lookup::Action(action) => {
+            app.pending_keys.clear();
+            run_action(app, action, music_database)
+        }
+        // Wait for the rest of the sequence
+        Lookup::Pending(_) => Ok(true),
+        Lookup::None if app.pending_keys.len() > 1 => {
+            // The keys before may be bound on their own, like `g` when `gg` is too
+            app.pending_keys.pop();
+            let earlier = match app.keymap.lookup(&app.pending_keys) {
+                Lookup::Pending(action) => action,
+                _ => None,
+            };
+            app.pending_keys.clear();
+            if let Some(action) = earlier {
+                if !run_action(app, action, music_database)? {
+                    return Ok(false);
+                }
+            }
+            press_key(app, key, music_database)
+        }
+        Lookup::None => {
+            app.pending_keys.clear();
+            Ok(true)
+        }
+    }
+}
+
+// Scroll, apply or close the organize or delete preview, or the help
+fn press_dialog_key(app: &mut App, key: Key) {
+    let page = app.window_height as i32 / 2;
+    let step = match app.keymap.lookup_dialog(key) {
+        Some(Action::DialogDown) => 1,
+        Some(Action::DialogUp) => -1,
+        Some(Action::DialogPageDown) => page,
+        Some(Action::DialogPageUp) => -page,
+        Some(Action::Confirm) => {
+            match app.mode {
+                Mode::Organize => app.apply_organize(),
+                Mode::Delete => app.apply_delete(),
+                _ => {}
+            }
+            return;
+        }
+        Some(Action::Cancel) => {
+            match app.mode {
+                Mode::Organize => app.cancel_organize(),
+                Mode::Delete => app.cancel_delete(),
+                _ => app.toggle_help(),
+            }
+            return;
+        }
+        // The key that opens the help closes it too
+        _ => {
+            if app.mode == Mode::Help
+                && matches!(app.keymap.lookup(&[key]), Lookup::Action(Action::Help))
+            {
+                app.toggle_help();
+            }
+            return;
+        }
+    };
+
+    match app.mode {
+        Mode::Organize => app.scroll_organize_preview(step),
+        Mode::Delete => app.scroll_delete_preview(step),
+        _ => app.scroll_help(step),
+    }
+}
+
+// A sequence left unfinished runs what its keys are bound to on their own
+pub fn handle_key_timeout(app: &mut App, music_database: &str) -> Result<bool, ExitFailure> {
+    if app.pending_keys.is_empty() || app.pending_keys_time.elapsed() < KEY_TIMEOUT {
+        return Ok(true);
+    }
+
+    let action = match app.keymap.lookup(&app.pending_keys) {
+        Lookup::Pending(action) => action,
+        _ => None,
+    };
+    app.pending_keys.clear();
+    match action {
+        Some(action) => run_action(app, action, music_database),
+        None => Ok(true),
+    }
+}
+
+// Returns false to exit the program
+fn run_action(app: &mut App, action: Action, music_database: &str) -> Result<bool, ExitFailure> {
+    if app.focus == Focus::PlayList {
+        let page = app.window_height as i32;
+        match action {
+            Action::Top => app.play_list_selection = 0,
+            Action::Bottom => app.select_play_list_bottom(),
+            Action::MoveDown => app.move_play_list_selection(1),
+            Action::MoveDownFast => app.move_play_list_selection(5),
+            Action::MoveUp => app.move_play_list_selection(-1),
+            Action::MoveUpFast => app.move_play_list_selection(-5),
+            Action::NextPage => app.move_play_list_selection(page),
+            Action::PreviousPage => app.move_play_list_selection(-page),
+            Action::AddToPlaylist | Action::PlayNow => app.play_selected_now(),
+            Action::PlayNext => app.play_selected_next(),
+            Action::MoveItemUp => app.move_play_list_item(-1),
+            Action::MoveItemDown => app.move_play_list_item(1),
+            Action::Remove => app.remove_selected_from_play_list(),
+            _ => return run_browser_action(app, action, music_database),
+        }
+        return Ok(true);
+    }
+
+    run_browser_action(app, action, music_database)
+}
+
+fn run_browser_action(
+    app: &mut App,
+    action: Action,
+    music_database: &str,
+) -> Result<bool, ExitFailure> {
+    match action {
+        Action::Quit => return Ok(false),
+        Action::Top => app.move_select_top(),
+        Action::Bottom => app.move_select_bottom(),
+        Action::MoveDown => app.move_select_down(1),
+        Action::MoveDownFast => app.move_select_down(5),
+        Action::MoveUp => app.move_select_up(1),
+        Action::MoveUpFast => app.move_select_up(5),
+        Action::NextPage => app.next_page(),
+        Action::PreviousPage => app.previous_page(),
+        Action::OpenFolder => app.open_folder(),
+        Action::BackFolder => app.back_previous_folder(music_database),
+        Action::AddToPlaylist => app.add_music_to_list(Insert::End),
+        Action::PlayNext => app.add_music_to_list(Insert::Next),
+        Action::PlayNow => app.add_music_to_list(Insert::Now),
+        Action::Pause => app.stop_or_start_play(),
+        Action::VolumeDown => app.update_volume(&|v| if v > 0.0 { v - 0.05 } else { 0.0 }),
+        Action::VolumeUp => app.update_volume(&|v| if v < 1.25 { v + 0.05 } else { 1.25 }),
+        Action::Search => app.set_mode(Mode::Search),
+        Action::Command => app.set_mode(Mode::Command),
+        Action::EditTags => app.open_tag_editor(),
+        Action::Visualizer => app.visualizer.toggle(),
+        Action::SwitchPane => app.switch_focus(),
+        Action::JumpToPlaying => app.jump_to_playing_music(),
+        Action::Mark => app.toggle_mark(),
+        Action::Visual => app.toggle_visual(),
+        Action::Undo => app.undo_play_list(),
+        Action::Redo => app.redo_play_list(),
+        Action::Help => app.toggle_help(),
+        Action::ToggleHidden => app.toggle_hidden(),
+        // Only for the play list
+        Action::MoveItemUp | Action::MoveItemDown | Action::Remove => {}
+        // Only for the dialogs, never looked up in the browser
+        Action::DialogDown
+        | Action::DialogUp
+        | Action::DialogPageDown
+        | Action::DialogPageUp
+        | Action::Confirm
+        | Action::Cancel => {}
+        Action::Clear => {
+            app.clear_marks();
+            app.dupes_report = None;
+            app.populate_files()?;
+            app.search_buffer = Vec::new();
+        }
+    }
+
+    Ok(true)
+}
@@ -0,0 +1,98 @@
+// Older states beyond this are forgotten
+const LIMIT: usize = 100;
+
+// Undo and redo stacks of whole snapshots of a state
+pub struct History<T> {
+    undo: Vec<T>,
+    redo: Vec<T>,
+}
+
+impl<T> History<T> {
+    pub fn new() -> History<T> {
+        History {
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    // Call with the state as it is before a change
+    pub fn save(&mut self, state: T) {
+        if self.undo.len() >= LIMIT {
+            self.undo.remove(0);
+        }
+        self.undo.push(state);
+        self.redo.clear();
+    }
+
+    // The state before the last change, `current` can then be redone
+    pub fn undo(&mut self, current: T) -> Option<T> {
+        let state = self.undo.pop()?;
+        self.redo.push(current);
+        Some(state)
+    }
+
+    pub fn redo(&mut self, current: T) -> Option<T> {
+        let state = self.redo.pop()?;
+        self.undo.push(current);
+        Some(state)
+    }
+
+    // Every saved state, to follow changes made outside of the history
+    pub fn states_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.undo.iter_mut().chain(self.redo.iter_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_then_redo() {
+        let mut history = History::new();
+        history.save(1);
+        history.save(2);
+        assert_eq!(history.undo(3), Some(2));
+        assert_eq!(history.undo(2), Some(1));
+        assert_eq!(history.undo(1), None);
+        assert_eq!(history.redo(1), Some(2));
+        assert_eq!(history.redo(2), Some(3));
+        assert_eq!(history.redo(3), None);
+    }
+
+    #[test]
+    fn a_new_change_clears_the_redo() {
+        let mut history = History::new();
+        history.save(1);
+        assert_eq!(history.undo(2), Some(1));
+        history.save(1);
+        assert_eq!(history.redo(5), None);
+        assert_eq!(history.undo(5), Some(1));
+    }
+
+    #[test]
+    fn the_oldest_states_are_forgotten() {
+        let mut history = History::new();
+        for state in 0..LIMIT + 5 {
+            history.save(state);
+        }
+        let mut undone = Vec::new();
+        while let Some(state) = history.undo(0) {
+            undone.push(state);
+        }
+        assert_eq!(undone.len(), LIMIT);
+        assert_eq!(undone.last(), Some(&5));
+    }
+
+    #[test]
+    fn saved_states_can_be_edited() {
+        let mut history = History::new();
+        history.save(1);
+        history.save(2);
+        history.undo(3);
+        history.states_mut().for_each(|state| *state *= 10);
+        assert_eq!(history.redo(0), Some(30));
+        assert_eq!(history.undo(0), Some(0));
+        assert_eq!(history.undo(0), Some(10));
+    }
+}
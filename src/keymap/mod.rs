@@ -0,0 +1,582 @@
+use std::collections::BTreeMap;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+// What the keys of the browse mode and of the dialogs can do, named as in the `keys:` config
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    MoveUp,
+    MoveUpFast,
+    MoveDown,
+    MoveDownFast,
+    Top,
+    Bottom,
+    NextPage,
+    PreviousPage,
+    OpenFolder,
+    BackFolder,
+    Search,
+    Command,
+    Quit,
+    Clear,
+    Pause,
+    VolumeDown,
+    VolumeUp,
+    AddToPlaylist,
+    PlayNext,
+    PlayNow,
+    EditTags,
+    Visualizer,
+    SwitchPane,
+    MoveItemUp,
+    MoveItemDown,
+    Remove,
+    JumpToPlaying,
+    Mark,
+    Visual,
+    Undo,
+    Redo,
+    Help,
+    ToggleHidden,
+    // The previews, the help and the tag editor
+    DialogDown,
+    DialogUp,
+    DialogPageDown,
+    DialogPageUp,
+    Confirm,
+    Cancel,
+}
+
+// Help order, config name and description of every action
+#[rustfmt::skip]
+const ACTIONS: &[(Action, &str, &str)] = &[
+    (Action::MoveUp, "move_up", "Move selection up"),
+    (Action::MoveUpFast, "move_up_fast", "Move selection up 5 steps"),
+    (Action::MoveDown, "move_down", "Move selection down"),
+    (Action::MoveDownFast, "move_down_fast", "Move selection down 5 steps"),
+    (Action::Top, "top", "Move selection to the top"),
+    (Action::Bottom, "bottom", "Move selection to the bottom"),
+    (Action::NextPage, "next_page", "Next page"),
+    (Action::PreviousPage, "previous_page", "Previous page"),
+    (Action::OpenFolder, "open_folder", "Open folder"),
+    (Action::BackFolder, "back_folder", "Back to previous folder"),
+    (Action::Search, "search", "Enter search mode"),
+    (Action::Command, "command", "Enter command mode"),
+    (Action::Quit, "quit", "Exit program"),
+    (Action::Clear, "clear", "Leave search results, duplicates or marks"),
+    (Action::Pause, "pause", "Pause or resume the music"),
+    (Action::VolumeDown, "volume_down", "Decrease volume"),
+    (Action::VolumeUp, "volume_up", "Increase volume"),
+    (Action::AddToPlaylist, "add_to_playlist", "Add music to the playlist / play it now"),
+    (Action::PlayNext, "play_next", "Play music after the playing song"),
+    (Action::PlayNow, "play_now", "Play music now, replacing the playing song"),
+    (Action::EditTags, "edit_tags", "Edit tags of the selection"),
+    (Action::Visualizer, "visualizer", "Spectrum, oscilloscope or nothing"),
+    (Action::SwitchPane, "switch_pane", "Switch between the files and the playlist"),
+    (Action::MoveItemUp, "move_item_up", "Move the selected song up the playlist"),
+    (Action::MoveItemDown, "move_item_down", "Move the selected song down the playlist"),
+    (Action::Remove, "remove", "Remove the selected song from the playlist"),
+    (Action::JumpToPlaying, "jump_to_playing", "Select the playing song in the playlist"),
+    (Action::Mark, "mark", "Mark or unmark the selection"),
+    (Action::Visual, "visual", "Start or end a visual selection"),
+    (Action::Undo, "undo", "Undo the last playlist edit"),
+    (Action::Redo, "redo", "Redo the last undone playlist edit"),
+    (Action::Help, "help", "Show every key and command"),
+    (Action::ToggleHidden, "toggle_hidden", "Show or hide hidden files"),
+    (Action::DialogDown, "dialog_down", "Scroll a preview or the help down, next tag field"),
+    (Action::DialogUp, "dialog_up", "Scroll a preview or the help up, previous tag field"),
+    (Action::DialogPageDown, "dialog_page_down", "Next page of a preview or the help"),
+    (Action::DialogPageUp, "dialog_page_up", "Previous page of a preview or the help"),
+    (Action::Confirm, "confirm", "Apply a preview, save the tag editor"),
+    (Action::Cancel, "cancel", "Close a preview, the help or the tag editor"),
+];
+
+const DEFAULT_KEYS: &[(&str, Action)] = &[
+    ("k", Action::MoveUp),
+    ("<Up>", Action::MoveUp),
+    ("K", Action::MoveUpFast),
+    ("j", Action::MoveDown),
+    ("<Down>", Action::MoveDown),
+    ("J", Action::MoveDownFast),
+    ("g", Action::Top),
+    ("G", Action::Bottom),
+    ("n", Action::NextPage),
+    ("N", Action::PreviousPage),
+    ("l", Action::OpenFolder),
+    ("h", Action::BackFolder),
+    ("|", Action::Search),
+    (":", Action::Command),
+    ("q", Action::Quit),
+    ("<Esc>", Action::Clear),
+    ("<Space>", Action::Pause),
+    ("-", Action::VolumeDown),
+    ("+", Action::VolumeUp),
+    ("=", Action::VolumeUp),
+    ("<Enter>", Action::AddToPlaylist),
+    ("p", Action::PlayNext),
+    ("P", Action::PlayNow),
+    ("t", Action::EditTags),
+    ("s", Action::Visualizer),
+    ("<Tab>", Action::SwitchPane),
+    ("<C-k>", Action::MoveItemUp),
+    ("<C-j>", Action::MoveItemDown),
+    ("d", Action::Remove),
+    ("o", Action::JumpToPlaying),
+    ("m", Action::Mark),
+    ("v", Action::Visual),
+    ("u", Action::Undo),
+    ("<C-r>", Action::Redo),
+    ("?", Action::Help),
+    (".", Action::ToggleHidden),
+    ("j", Action::DialogDown),
+    ("<Down>", Action::DialogDown),
+    ("<Tab>", Action::DialogDown),
+    ("k", Action::DialogUp),
+    ("<Up>", Action::DialogUp),
+    ("<S-Tab>", Action::DialogUp),
+    ("n", Action::DialogPageDown),
+    ("N", Action::DialogPageUp),
+    ("<Enter>", Action::Confirm),
+    ("<Esc>", Action::Cancel),
+    ("q", Action::Cancel),
+];
+
+// Names between `<` and `>`
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("Space", KeyCode::Char(' ')),
+    ("Enter", KeyCode::Enter),
+    ("CR", KeyCode::Enter),
+    ("Esc", KeyCode::Esc),
+    ("Tab", KeyCode::Tab),
+    ("BackTab", KeyCode::BackTab),
+    ("BS", KeyCode::Backspace),
+    ("Backspace", KeyCode::Backspace),
+    ("Del", KeyCode::Delete),
+    ("Insert", KeyCode::Insert),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("lt", KeyCode::Char('<')),
+];
+
+impl Action {
+    fn from_name(name: &str) -> Option<Action> {
+        ACTIONS
+            .iter()
+            .find(|(_, action_name, _)| *action_name == name)
+            .map(|(action, _, _)| *action)
+    }
+
+    // Dialog keys are bound apart, `j` scrolls a preview and moves in the browser
+    fn is_dialog(self) -> bool {
+        matches!(
+            self,
+            Action::DialogDown
+                | Action::DialogUp
+                | Action::DialogPageDown
+                | Action::DialogPageUp
+                | Action::Confirm
+                | Action::Cancel
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    // Shift goes into the case of characters, control ones are lowercase
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Key {
+        let mut modifiers =
+            modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        let code = match code {
+            KeyCode::Char(chr) => {
+                let shift = modifiers.contains(KeyModifiers::SHIFT);
+                modifiers.remove(KeyModifiers::SHIFT);
+                if modifiers.contains(KeyModifiers::CONTROL) {
+                    KeyCode::Char(chr.to_ascii_lowercase())
+                } else if shift {
+                    KeyCode::Char(chr.to_ascii_uppercase())
+                } else {
+                    KeyCode::Char(chr)
+                }
+            }
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            KeyCode::BackTab => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            code => code,
+        };
+
+        Key { code, modifiers }
+    }
+
+    pub fn from_event(event: KeyEvent) -> Key {
+        Key::new(event.code, event.modifiers)
+    }
+
+    // `<C-d>`, `<A-Enter>`, `<S-Up>` or a plain character
+    fn to_notation(self) -> String {
+        let name = match self.code {
+            KeyCode::Char('<') => String::from("lt"),
+            KeyCode::Char(' ') => String::from("Space"),
+            KeyCode::Char(chr) if self.modifiers.is_empty() => return chr.to_string(),
+            KeyCode::Char(chr) => chr.to_string(),
+            KeyCode::BackTab => String::from("S-Tab"),
+            KeyCode::F(n) => format!("F{}", n),
+            code => KEY_NAMES
+                .iter()
+                .find(|(_, key_code)| *key_code == code)
+                .map(|(name, _)| name.to_string())
+                .unwrap_or_else(|| String::from("?")),
+        };
+
+        let mut notation = String::from("<");
+        for (modifier, prefix) in [
+            (KeyModifiers::CONTROL, "C-"),
+            (KeyModifiers::ALT, "A-"),
+            (KeyModifiers::SHIFT, "S-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                notation.push_str(prefix);
+            }
+        }
+        notation.push_str(&name);
+        notation.push('>');
+        notation
+    }
+}
+
+// Vim like key sequences: `gg`, `<C-d>`, `<A-j>`, `<Space>`, `<lt>` for `<`
+pub fn parse_keys(text: &str) -> Result<Vec<Key>, String> {
+    let mut keys = Vec::new();
+    let mut chars = text.chars();
+    while let Some(chr) = chars.next() {
+        if chr != '<' || !chars.as_str().contains('>') {
+            keys.push(Key::new(KeyCode::Char(chr), KeyModifiers::NONE));
+            continue;
+        }
+
+        let inside: String = chars.by_ref().take_while(|chr| *chr != '>').collect();
+        let mut modifiers = KeyModifiers::NONE;
+        let mut name = inside.as_str();
+        loop {
+            let modifier = match name.get(..2) {
+                Some("C-") | Some("c-") => KeyModifiers::CONTROL,
+                Some("A-") | Some("a-") | Some("M-") | Some("m-") => KeyModifiers::ALT,
+                Some("S-") | Some("s-") => KeyModifiers::SHIFT,
+                _ => break,
+            };
+            if name.len() == 2 {
+                break;
+            }
+            modifiers |= modifier;
+            name = &name[2..];
+        }
+
+        let code = if name.chars().count() == 1 {
+            KeyCode::Char(name.chars().next().unwrap())
+        } else if let Some(n) = name.strip_prefix('F').and_then(|n| n.parse::<u8>().ok()) {
+            KeyCode::F(n)
+        } else {
+            match KEY_NAMES
+                .iter()
+                .find(|(key_name, _)| key_name.eq_ignore_ascii_case(name))
+            {
+                Some((_, code)) => *code,
+                None => return Err(format!("Unknown key <{}>", inside)),
+            }
+        };
+        keys.push(Key::new(code, modifiers));
+    }
+
+    if keys.is_empty() {
+        return Err(String::from("Empty key sequence"));
+    }
+    Ok(keys)
+}
+
+pub enum Lookup {
+    Action(Action),
+    // The keys start a longer sequence, `Action` if they are bound on their own too
+    Pending(Option<Action>),
+    None,
+}
+
+pub struct Keymap {
+    bindings: Vec<(Vec<Key>, Action)>,
+}
+
+impl Keymap {
+    // The default keys with the `keys:` of the config on top, `none` unbinds a sequence
+    pub fn new(config_keys: &BTreeMap<String, String>) -> (Keymap, Option<String>) {
+        let mut keymap = Keymap {
+            bindings: DEFAULT_KEYS
+                .iter()
+                .map(|(keys, action)| (parse_keys(keys).unwrap(), *action))
+                .collect(),
+        };
+
+        let mut error = None;
+        for (keys, action) in config_keys {
+            let result = parse_keys(keys).and_then(|keys| match action.as_str() {
+                "none" => {
+                    keymap.bindings.retain(|(bound, _)| *bound != keys);
+                    Ok(())
+                }
+                name => match Action::from_name(name) {
+                    Some(action) => {
+                        keymap.bindings.retain(|(bound, bound_action)| {
+                            *bound != keys || bound_action.is_dialog() != action.is_dialog()
+                        });
+                        keymap.bindings.push((keys, action));
+                        Ok(())
+                    }
+                    None => Err(format!("Unknown action {}", name)),
+                },
+            });
+            if let Err(err) = result {
+                error = Some(format!("keys: {}: {}", keys, err));
+            }
+        }
+
+        (keymap, error)
+    }
+
+    fn browse_bindings(&self) -> impl Iterator<Item = &(Vec<Key>, Action)> {
+        self.bindings
+            .iter()
+            .filter(|(_, action)| !action.is_dialog())
+    }
+
+    pub fn lookup(&self, keys: &[Key]) -> Lookup {
+        let exact = self
+            .browse_bindings()
+            .find(|(bound, _)| bound == keys)
+            .map(|(_, action)| *action);
+        let longer = self
+            .browse_bindings()
+            .any(|(bound, _)| bound.len() > keys.len() && bound.starts_with(keys));
+
+        match (exact, longer) {
+            (_, true) => Lookup::Pending(exact),
+            (Some(action), false) => Lookup::Action(action),
+            (None, false) => Lookup::None,
+        }
+    }
+
+    // Dialogs take single keys
+    pub fn lookup_dialog(&self, key: Key) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(bound, action)| action.is_dialog() && *bound == [key])
+            .map(|(_, action)| *action)
+    }
+
+    // The first keys bound to the action, for the titles of the dialogs
+    pub fn key_of(&self, action: Action) -> String {
+        self.bindings
+            .iter()
+            .find(|(_, bound)| *bound == action)
+            .map_or(String::from("none"), |(keys, _)| {
+                keys.iter().map(|key| key.to_notation()).collect()
+            })
+    }
+
+    // Description and bound keys of every action that has some, e.g. `[k, <Up>]`
+    pub fn help(&self) -> Vec<(&'static str, String)> {
+        let mut help: Vec<(&'static str, String)> = ACTIONS
+            .iter()
+            .filter_map(|(action, _, description)| {
+                let keys: Vec<String> = self
+                    .bindings
+                    .iter()
+                    .filter(|(_, bound)| bound == action)
+                    .map(|(keys, _)| keys.iter().map(|key| key.to_notation()).collect())
+                    .collect();
+                if keys.is_empty() {
+                    None
+                } else {
+                    Some((*description, format!("[{}]", keys.join(", "))))
+                }
+            })
+            .collect();
+        // Not part of the browse mode
+        help.push(("Exit search or command mode", String::from("[<Esc>]")));
+
+        help
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Key {
+        Key { code, modifiers }
+    }
+
+    fn char(chr: char) -> Key {
+        key(KeyCode::Char(chr), KeyModifiers::NONE)
+    }
+
+    fn config(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(keys, action)| (keys.to_string(), action.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn notation() {
+        assert_eq!(parse_keys("gg").unwrap(), [char('g'), char('g')]);
+        assert_eq!(
+            parse_keys("<C-d>").unwrap(),
+            [key(KeyCode::Char('d'), KeyModifiers::CONTROL)]
+        );
+        assert_eq!(
+            parse_keys("<c-a-Enter>").unwrap(),
+            [key(
+                KeyCode::Enter,
+                KeyModifiers::CONTROL | KeyModifiers::ALT
+            )]
+        );
+        assert_eq!(
+            parse_keys("<M-j>").unwrap(),
+            [key(KeyCode::Char('j'), KeyModifiers::ALT)]
+        );
+        assert_eq!(parse_keys("<space><lt>").unwrap(), [char(' '), char('<')]);
+        assert_eq!(
+            parse_keys("<F12>").unwrap(),
+            [key(KeyCode::F(12), KeyModifiers::NONE)]
+        );
+        // Shift goes into the character, or makes <Tab> a <BackTab>
+        assert_eq!(parse_keys("<S-a>").unwrap(), [char('A')]);
+        assert_eq!(
+            parse_keys("<S-Tab>").unwrap(),
+            [key(KeyCode::BackTab, KeyModifiers::NONE)]
+        );
+        // A lone `<` or a `-` on its own are plain characters
+        assert_eq!(parse_keys("<").unwrap(), [char('<')]);
+        assert_eq!(
+            parse_keys("<C-->").unwrap(),
+            [key(KeyCode::Char('-'), KeyModifiers::CONTROL)]
+        );
+    }
+
+    #[test]
+    fn bad_notation() {
+        assert!(parse_keys("").is_err());
+        assert!(parse_keys("<Nope>").is_err());
+        assert!(parse_keys("<C-Nope>").is_err());
+    }
+
+    #[test]
+    fn notation_round_trip() {
+        for text in [
+            "j",
+            "<C-d>",
+            "<A-Enter>",
+            "<S-Tab>",
+            "<Space>",
+            "<lt>",
+            "<F5>",
+            "<C-A-x>",
+        ] {
+            let keys = parse_keys(text).unwrap();
+            let notation: String = keys.iter().map(|key| key.to_notation()).collect();
+            assert_eq!(notation, text);
+        }
+    }
+
+    #[test]
+    fn events_match_the_notation() {
+        let event = |code, modifiers| Key::from_event(KeyEvent::new(code, modifiers));
+        assert_eq!(
+            event(KeyCode::Char('G'), KeyModifiers::SHIFT),
+            parse_keys("G").unwrap()[0]
+        );
+        assert_eq!(
+            event(
+                KeyCode::Char('D'),
+                KeyModifiers::CONTROL | KeyModifiers::SHIFT
+            ),
+            parse_keys("<C-d>").unwrap()[0]
+        );
+        assert_eq!(
+            event(KeyCode::BackTab, KeyModifiers::SHIFT),
+            parse_keys("<S-Tab>").unwrap()[0]
+        );
+    }
+
+    #[test]
+    fn sequences() {
+        let (keymap, error) = Keymap::new(&config(&[("gg", "top"), ("<C-d>", "next_page")]));
+        assert!(error.is_none());
+
+        let g = [char('g')];
+        assert!(matches!(
+            keymap.lookup(&g),
+            Lookup::Pending(Some(Action::Top))
+        ));
+        assert!(matches!(
+            keymap.lookup(&[char('g'), char('g')]),
+            Lookup::Action(Action::Top)
+        ));
+        assert!(matches!(
+            keymap.lookup(&parse_keys("<C-d>").unwrap()),
+            Lookup::Action(Action::NextPage)
+        ));
+        assert!(matches!(keymap.lookup(&[char('z')]), Lookup::None));
+    }
+
+    #[test]
+    fn config_rebinds_and_unbinds() {
+        let (keymap, error) =
+            Keymap::new(&config(&[("q", "none"), ("x", "quit"), ("y", "confirm")]));
+        assert!(error.is_none());
+        assert!(matches!(keymap.lookup(&[char('q')]), Lookup::None));
+        assert!(matches!(
+            keymap.lookup(&[char('x')]),
+            Lookup::Action(Action::Quit)
+        ));
+        assert_eq!(keymap.lookup_dialog(char('q')), None);
+        assert_eq!(keymap.lookup_dialog(char('y')), Some(Action::Confirm));
+        assert_eq!(keymap.key_of(Action::Quit), "x");
+
+        let (_, error) = Keymap::new(&config(&[("<Bad>", "quit")]));
+        assert!(error.is_some());
+        let (_, error) = Keymap::new(&config(&[("x", "fly")]));
+        assert_eq!(error.as_deref(), Some("keys: x: Unknown action fly"));
+    }
+
+    #[test]
+    fn dialog_keys_apart_from_the_browser() {
+        let (keymap, _) = Keymap::new(&config(&[("j", "move_up")]));
+        assert!(matches!(
+            keymap.lookup(&[char('j')]),
+            Lookup::Action(Action::MoveUp)
+        ));
+        assert_eq!(keymap.lookup_dialog(char('j')), Some(Action::DialogDown));
+        assert_eq!(
+            keymap.lookup_dialog(key(KeyCode::Enter, KeyModifiers::NONE)),
+            Some(Action::Confirm)
+        );
+        assert!(matches!(
+            keymap.lookup(&[key(KeyCode::Enter, KeyModifiers::NONE)]),
+            Lookup::Action(Action::AddToPlaylist)
+        ));
+    }
+}
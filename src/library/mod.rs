@@ -0,0 +1,24 @@
+use std::io;
+use std::path::Path;
+
+use crate::file_ops::{get_audio_files_recursive, FileFilter};
+use crate::music::Music;
+
+// Every readable track below the music database
+pub struct Library {
+    pub tracks: Vec<Music>,
+}
+
+impl Library {
+    pub fn scan(music_database: &str, filter: &FileFilter) -> Result<Library, io::Error> {
+        let mut tracks = Vec::new();
+        for path in get_audio_files_recursive(Path::new(music_database), filter)? {
+            // Files whose tags can not be read are left out of the index
+            if let Ok(music) = Music::new(&path) {
+                tracks.push(music);
+            }
+        }
+
+        Ok(Library { tracks })
+    }
+}
@@ -0,0 +1,278 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::music::Music;
+
+pub struct Line {
+    // None for lyrics without timestamps
+    pub time: Option<Duration>,
+    pub text: String,
+}
+
+pub struct Lyrics {
+    pub lines: Vec<Line>,
+    // The `[offset:]` of the LRC file in milliseconds, positive shows lines sooner
+    pub offset: i64,
+}
+
+impl Lyrics {
+    // LRC text, `[mm:ss.xx]` tags before a line give the times it is sung;
+    // text without any tag is kept as unsynchronized lyrics
+    pub fn parse(text: &str) -> Lyrics {
+        let mut lines: Vec<Line> = Vec::new();
+        let mut offset = 0;
+        let mut synced = false;
+
+        for raw_line in text.lines() {
+            let mut rest = raw_line.trim();
+            let mut times: Vec<Duration> = Vec::new();
+            while let Some(tag) = rest.strip_prefix('[') {
+                let end = match tag.find(']') {
+                    Some(end) => end,
+                    None => break,
+                };
+                let content = &tag[..end];
+                if let Some(time) = parse_time(content) {
+                    times.push(time);
+                } else if let Some(value) = content.strip_prefix("offset:") {
+                    offset = value.trim().parse::<i64>().unwrap_or(0);
+                }
+                rest = tag[end + 1..].trim_start();
+            }
+
+            if times.is_empty() {
+                // Metadata like [ar:Artist] alone on a line
+                if !raw_line.trim_start().starts_with('[') {
+                    lines.push(Line {
+                        time: None,
+                        text: rest.to_string(),
+                    });
+                }
+            } else {
+                synced = true;
+                for time in times {
+                    lines.push(Line {
+                        time: Some(time),
+                        text: rest.to_string(),
+                    });
+                }
+            }
+        }
+
+        if synced {
+            lines.retain(|line| line.time.is_some());
+            lines.sort_by_key(|line| line.time);
+        }
+
+        Lyrics { lines, offset }
+    }
+
+    fn from_timed(content: &[(u32, String)]) -> Lyrics {
+        let mut lines: Vec<Line> = content
+            .iter()
+            .map(|(ms, text)| Line {
+                time: Some(Duration::from_millis(*ms as u64)),
+                text: text.trim().to_string(),
+            })
+            .collect();
+        lines.sort_by_key(|line| line.time);
+
+        Lyrics { lines, offset: 0 }
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.lines.iter().any(|line| line.time.is_some())
+    }
+
+    // The last line whose time has come, `offset` in milliseconds is added to the file's own
+    pub fn current_line(&self, position: Duration, offset: i64) -> Option<usize> {
+        let position = position.as_millis() as i64 + self.offset + offset;
+        self.lines.iter().rposition(|line| match line.time {
+            Some(time) => time.as_millis() as i64 <= position,
+            None => false,
+        })
+    }
+}
+
+// `mm:ss`, `mm:ss.xx` or `mm:ss:xx`
+fn parse_time(tag: &str) -> Option<Duration> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    let minutes = minutes.trim().parse::<u64>().ok()?;
+    let seconds = seconds.trim().replacen(':', ".", 1);
+    let seconds = seconds.parse::<f64>().ok()?;
+    if !(0.0..60.0).contains(&seconds) {
+        return None;
+    }
+
+    Duration::from_secs(minutes.checked_mul(60)?).checked_add(Duration::from_secs_f64(seconds))
+}
+
+// A `.lrc` with the same name as the music, then the lyrics in its tags
+pub fn load(path: &str) -> Option<Lyrics> {
+    let file = Path::new(path);
+    for extension in ["lrc", "LRC"] {
+        if let Ok(text) = fs::read_to_string(file.with_extension(extension)) {
+            return Some(Lyrics::parse(&text));
+        }
+    }
+
+    let extension = file.extension()?.to_string_lossy().to_lowercase();
+    let lyrics = match extension.as_ref() {
+        "mp3" => {
+            let tag = id3::Tag::read_from_path(path).ok()?;
+            // SYLT, unless its times are in MPEG frames
+            let synced = tag.synchronised_lyrics().find(|lyrics| {
+                lyrics.timestamp_format == id3::frame::TimestampFormat::MS
+                    && !lyrics.content.is_empty()
+            });
+            match synced {
+                Some(lyrics) => Lyrics::from_timed(&lyrics.content),
+                None => Lyrics::parse(&tag.lyrics().next()?.text),
+            }
+        }
+        "flac" => {
+            let tag = metaflac::Tag::read_from_path(path).ok()?;
+            let text = tag
+                .get_vorbis("LYRICS")
+                .or_else(|| tag.get_vorbis("UNSYNCEDLYRICS"))?
+                .next()?
+                .to_string();
+            Lyrics::parse(&text)
+        }
+        "m4a" | "m4b" | "m4p" | "m4v" | "mp4" => {
+            Lyrics::parse(mp4ameta::Tag::read_from_path(path).ok()?.lyrics()?)
+        }
+        _ => return None,
+    };
+
+    if lyrics.lines.is_empty() {
+        None
+    } else {
+        Some(lyrics)
+    }
+}
+
+// Lyrics of the playing music and how the panel shows them
+pub struct LyricsPanel {
+    pub visible: bool,
+    // Milliseconds added to the lyrics' own offset, for the playing music only
+    pub offset: i64,
+    music_path: Option<String>,
+    lyrics: Option<Lyrics>,
+}
+
+impl LyricsPanel {
+    pub fn new() -> LyricsPanel {
+        LyricsPanel {
+            visible: false,
+            offset: 0,
+            music_path: None,
+            lyrics: None,
+        }
+    }
+
+    pub fn update(&mut self, playing_music: Option<&Music>) {
+        let music_path = playing_music.map(|music| music.path.clone());
+        // Only read when shown, the panel reloads once opened
+        if !self.visible || music_path == self.music_path {
+            return;
+        }
+
+        self.lyrics = music_path.as_deref().and_then(load);
+        self.offset = 0;
+        self.music_path = music_path;
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        if !self.visible {
+            self.music_path = None;
+            self.lyrics = None;
+        }
+    }
+
+    pub fn lyrics(&self) -> Option<&Lyrics> {
+        self.lyrics.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn times(lyrics: &Lyrics) -> Vec<Option<u128>> {
+        lyrics
+            .lines
+            .iter()
+            .map(|line| line.time.map(|time| time.as_millis()))
+            .collect()
+    }
+
+    fn texts(lyrics: &Lyrics) -> Vec<&str> {
+        lyrics.lines.iter().map(|line| line.text.as_str()).collect()
+    }
+
+    #[test]
+    fn time_tags() {
+        assert_eq!(parse_time("01:02"), Some(Duration::from_secs(62)));
+        assert_eq!(parse_time("01:02.50"), Some(Duration::from_millis(62_500)));
+        assert_eq!(parse_time(" 1:02:25"), Some(Duration::from_millis(62_250)));
+        assert_eq!(parse_time("120:00"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_time("01:60"), None);
+        assert_eq!(parse_time("ar:Artist"), None);
+        assert_eq!(parse_time("01"), None);
+        assert_eq!(parse_time("-1:00"), None);
+        assert_eq!(parse_time("999999999999999999:00"), None);
+    }
+
+    #[test]
+    fn synced_lines_in_time_order() {
+        let lyrics = Lyrics::parse(
+            "[ar:Artist]\n[ti:Title]\n[00:12.00]Second\n[00:01.00] First \n\
+             [00:20.00][00:30.00]Chorus\nno time\n[00:40.00]",
+        );
+
+        assert!(lyrics.is_synced());
+        assert_eq!(
+            times(&lyrics),
+            [
+                Some(1000),
+                Some(12000),
+                Some(20000),
+                Some(30000),
+                Some(40000)
+            ]
+        );
+        assert_eq!(texts(&lyrics), ["First", "Second", "Chorus", "Chorus", ""]);
+    }
+
+    #[test]
+    fn unsynced_text() {
+        let lyrics = Lyrics::parse("[ar:Artist]\nOne\n\n[unclosed\nTwo");
+
+        assert!(!lyrics.is_synced());
+        assert_eq!(times(&lyrics), [None, None, None]);
+        assert_eq!(texts(&lyrics), ["One", "", "Two"]);
+    }
+
+    #[test]
+    fn offset_and_current_line() {
+        let lyrics = Lyrics::parse("[offset:+500]\n[00:01.00]A\n[00:02.00]B\n[00:03.00]C");
+        assert_eq!(lyrics.offset, 500);
+
+        let at =
+            |millis: u64, offset: i64| lyrics.current_line(Duration::from_millis(millis), offset);
+        assert_eq!(at(0, 0), None);
+        assert_eq!(at(499, 0), None);
+        assert_eq!(at(500, 0), Some(0));
+        assert_eq!(at(1600, 0), Some(1));
+        assert_eq!(at(1600, -200), Some(0));
+        assert_eq!(at(60_000, 0), Some(2));
+    }
+
+    #[test]
+    fn bad_offset_is_ignored() {
+        assert_eq!(Lyrics::parse("[offset:soon]\n[00:01]A").offset, 0);
+    }
+}
@@ -2,30 +2,51 @@ use std::io;
 use std::time::Duration;
 
 use app::App;
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::execute;
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
 use exitfailure::ExitFailure;
-use handler::event::handle_event;
+use handler::event::{handle_event, handle_key_timeout};
 use rodio::OutputStream;
 use tui::backend::CrosstermBackend;
 use tui::Terminal;
 use view::handle_theme;
 
 mod app;
+mod command_line;
 mod commands;
 mod config;
+mod cover;
+mod dupes;
 mod file_ops;
 mod handler;
+mod history;
+mod keymap;
+mod library;
+mod lyrics;
 mod music;
+mod organize;
+mod scrobbler;
+mod session;
+mod smart;
+mod sort;
+mod stats;
+mod tag_editor;
 mod utils;
 mod view;
+mod visualizer;
+mod waveform;
 
 fn main() -> Result<(), ExitFailure> {
     let init_config = config::init()?;
-    let theme = handle_theme(init_config.theme);
+    let theme = handle_theme(&init_config.theme);
 
     // Initialize terminal
     enable_raw_mode()?;
-    let stdout = io::stdout();
+    let mut stdout = io::stdout();
+    if init_config.mouse {
+        execute!(stdout, EnableMouseCapture)?;
+    }
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
@@ -33,20 +54,33 @@ fn main() -> Result<(), ExitFailure> {
 
     // Initialize App state
     let (_stream, stream_handle) = OutputStream::try_default()?;
-    let mut app = App::new(&mut terminal, &init_config.music_database, stream_handle)?;
+    let mut app = App::new(&mut terminal, &init_config, stream_handle)?;
 
     loop {
         app.update_window_height();
         view::draw(&mut app, &theme)?;
 
-        if crossterm::event::poll(Duration::from_millis(100))? {
+        // The visualizer needs more frames than the progress bar
+        let timeout = if app.visualizer.mode.is_some() {
+            40
+        } else {
+            100
+        };
+        if crossterm::event::poll(Duration::from_millis(timeout))? {
             if !handle_event(&mut app, &init_config.music_database)? {
                 break;
             };
         }
+        if !handle_key_timeout(&mut app, &init_config.music_database)? {
+            break;
+        }
         app.check_music_list();
     }
+    app.save_session();
 
+    if init_config.mouse {
+        execute!(io::stdout(), DisableMouseCapture)?;
+    }
     disable_raw_mode()?;
 
     Ok(())
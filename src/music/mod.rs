@@ -3,6 +3,9 @@ use std::time::{Instant, Duration};
 use crate::file_ops::read_audio_file;
 use crate::utils::split_path::split_path_to_name;
 
+// A play counts once half the track or this much of it has been heard
+const LISTENED_DURATION: Duration = Duration::from_secs(4 * 60);
+
 #[derive(PartialEq, Clone)]
 pub struct Music {
     pub path: String,
@@ -10,9 +13,16 @@ pub struct Music {
     pub artist: String,
     pub title: String,
     pub album: String,
+    pub album_artist: String,
+    pub genre: String,
+    pub year: Option<i32>,
+    pub track_number: Option<u16>,
+    pub disc_number: Option<u16>,
     pub play_position: Duration,
     pub total_duration: Duration,
     pub start_time: Option<Instant>,
+    // Whether the current play has already been counted
+    pub play_recorded: bool,
 }
 
 impl Music {
@@ -28,11 +38,22 @@ impl Music {
                 artist: audio.artist,
                 title: audio.title,
                 album: audio.album,
+                album_artist: audio.album_artist,
+                genre: audio.genre,
+                year: audio.year,
+                track_number: audio.track_number,
+                disc_number: audio.disc_number,
                 play_position: Duration::from_secs(0),
                 total_duration: audio.duration,
                 start_time: None,
+                play_recorded: false,
             }),
             Err(err) => Err(err),
         }
     }
+
+    pub fn is_listened(&self) -> bool {
+        self.play_position >= LISTENED_DURATION
+            || (self.total_duration.as_secs() > 0 && self.play_position >= self.total_duration / 2)
+    }
 }
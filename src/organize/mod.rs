@@ -0,0 +1,379 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::config_dir;
+use crate::file_ops::{get_audio_files_recursive, FileFilter};
+use crate::music::Music;
+
+const JOURNAL_FILE: &str = "organize_journal.yml";
+
+// The moves waiting for confirmation and how far the preview is scrolled
+pub struct Preview {
+    pub pattern: String,
+    pub moves: Vec<Move>,
+    pub scroll: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Move {
+    pub from: String,
+    pub to: String,
+    // Another file already uses the target path, the move is left out
+    #[serde(skip)]
+    pub conflict: bool,
+}
+
+// Work out where every file below the music database goes according to `pattern`,
+// e.g. `{albumartist}/{year} - {album}/{track:02} {title}.{ext}`
+pub fn plan(music_database: &str, pattern: &str, filter: &FileFilter) -> Result<Vec<Move>, String> {
+    if pattern.trim().is_empty() {
+        return Err(String::from("Usage: organize <pattern>"));
+    }
+
+    let paths = get_audio_files_recursive(Path::new(music_database), filter)
+        .map_err(|err| err.to_string())?;
+    let mut moves: Vec<Move> = Vec::new();
+    for path in &paths {
+        let music = match Music::new(path) {
+            Ok(music) => music,
+            Err(_) => continue,
+        };
+
+        let mut relative = render(pattern, &music)?;
+        let extension = extension(path);
+        if !extension.is_empty() && !relative.ends_with(&format!(".{}", extension)) {
+            relative = format!("{}.{}", relative, extension);
+        }
+        let mut to = PathBuf::from(music_database);
+        to.push(relative);
+        let to = String::from(to.to_string_lossy());
+
+        if to != *path {
+            moves.push(Move {
+                from: path.clone(),
+                to,
+                conflict: false,
+            });
+        }
+    }
+
+    // Never overwrite an existing file, even one that is about to move away,
+    // nor let two files end up at the same path
+    let mut taken: HashSet<String> = HashSet::new();
+    for m in &mut moves {
+        m.conflict = Path::new(&m.to).exists() || taken.contains(&m.to);
+        taken.insert(m.to.clone());
+    }
+
+    Ok(moves)
+}
+
+fn extension(path: &str) -> String {
+    match Path::new(path).extension() {
+        Some(extension) => extension.to_string_lossy().to_string(),
+        None => String::new(),
+    }
+}
+
+fn render(pattern: &str, music: &Music) -> Result<String, String> {
+    // `Music::name` stops at the first dot, `Mr. Brightside.mp3` needs the whole stem
+    let file_name = match Path::new(&music.path).file_stem() {
+        Some(stem) => stem.to_string_lossy().to_string(),
+        None => music.name.clone(),
+    };
+    let mut rendered = String::new();
+    let mut rest = pattern;
+
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => return Err(String::from("Unclosed '{' in pattern")),
+        };
+        let placeholder = &rest[start + 1..end];
+        let (name, width) = match placeholder.split_once(':') {
+            Some((name, width)) => (name, width.parse::<usize>().ok()),
+            None => (placeholder, None),
+        };
+
+        let number = |number: Option<u16>| match (number, width) {
+            (Some(number), Some(width)) => format!("{:0width$}", number, width = width),
+            (Some(number), None) => number.to_string(),
+            (None, width) => format!("{:0width$}", 0, width = width.unwrap_or(1)),
+        };
+        let text = |text: &str| {
+            if text.trim().is_empty() {
+                String::from("Unknown")
+            } else {
+                text.trim().to_string()
+            }
+        };
+        let value = match name.to_ascii_lowercase().as_ref() {
+            "artist" => text(&music.artist),
+            "albumartist" => {
+                if music.album_artist.trim().is_empty() {
+                    text(&music.artist)
+                } else {
+                    text(&music.album_artist)
+                }
+            }
+            "album" => text(&music.album),
+            "title" => {
+                if music.title.trim().is_empty() {
+                    text(&file_name)
+                } else {
+                    text(&music.title)
+                }
+            }
+            "genre" => text(&music.genre),
+            "name" => text(&file_name),
+            "year" => match music.year {
+                Some(year) => year.to_string(),
+                None => String::from("Unknown"),
+            },
+            "track" => number(music.track_number),
+            "disc" => number(music.disc_number),
+            "ext" => extension(&music.path),
+            _ => return Err(format!("Unknown placeholder '{{{}}}'", name)),
+        };
+        rendered.push_str(&sanitize(&value));
+        rest = &rest[end + 1..];
+    }
+    rendered.push_str(rest);
+
+    let relative = Path::new(&rendered);
+    if relative.is_absolute() || rendered.split('/').any(|part| part == "..") {
+        return Err(String::from(
+            "The pattern must stay inside the music database",
+        ));
+    }
+
+    Ok(rendered)
+}
+
+// Tag values must not add path components or characters some file systems reject
+fn sanitize(value: &str) -> String {
+    let value: String = value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    value.trim_matches(|c| c == '.' || c == ' ').to_string()
+}
+
+fn move_file(from: &str, to: &str) -> Result<(), String> {
+    if let Some(parent) = Path::new(to).parent() {
+        fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+    }
+    if fs::rename(from, to).is_err() {
+        // Across file systems rename fails, copy instead
+        fs::copy(from, to).map_err(|err| err.to_string())?;
+        fs::remove_file(from).map_err(|err| err.to_string())?;
+    }
+
+    Ok(())
+}
+
+// Remove folders left empty, up to (not including) the music database
+fn remove_empty_parents(path: &str, music_database: &str) {
+    let root = Path::new(music_database);
+    let mut dir = Path::new(path).parent();
+    while let Some(current) = dir {
+        if current == root || !current.starts_with(root) || fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
+
+// Move the files, returns the moves done and the errors met
+pub fn apply(moves: &[Move], music_database: &str) -> (Vec<Move>, Vec<String>) {
+    let mut done = Vec::new();
+    let mut errors = Vec::new();
+    for m in moves {
+        if m.conflict {
+            errors.push(format!("Skipped {}, {} is taken", m.from, m.to));
+            continue;
+        }
+        match move_file(&m.from, &m.to) {
+            Ok(_) => {
+                remove_empty_parents(&m.from, music_database);
+                done.push(m.clone());
+            }
+            Err(err) => errors.push(format!("{}: {}", m.from, err)),
+        }
+    }
+
+    (done, errors)
+}
+
+fn journal_path() -> Option<PathBuf> {
+    config_dir().map(|mut path| {
+        path.push(JOURNAL_FILE);
+        path
+    })
+}
+
+// Every organize run, the latest last
+fn load_journal() -> Vec<Vec<Move>> {
+    match journal_path().map(File::open) {
+        Some(Ok(file)) => serde_yaml::from_reader(file).unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+fn save_journal(journal: &[Vec<Move>]) -> Result<(), String> {
+    if let Some(path) = journal_path() {
+        let content = serde_yaml::to_string(journal).map_err(|err| err.to_string())?;
+        fs::write(path, content).map_err(|err| err.to_string())?;
+    }
+
+    Ok(())
+}
+
+pub fn record(moves: Vec<Move>) -> Result<(), String> {
+    let mut journal = load_journal();
+    journal.push(moves);
+    save_journal(&journal)
+}
+
+// Move the files of the latest run back, returns the reverted moves and the errors met
+pub fn undo(music_database: &str) -> Result<(Vec<Move>, Vec<String>), String> {
+    let mut journal = load_journal();
+    let moves = match journal.pop() {
+        Some(moves) => moves,
+        None => return Err(String::from("Nothing to undo")),
+    };
+
+    let reverse: Vec<Move> = moves
+        .iter()
+        .rev()
+        .map(|m| Move {
+            from: m.to.clone(),
+            to: m.from.clone(),
+            conflict: Path::new(&m.from).exists(),
+        })
+        .collect();
+    let (done, errors) = apply(&reverse, music_database);
+    save_journal(&journal)?;
+
+    Ok((done, errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn music() -> Music {
+        Music {
+            path: String::from("/music/in/01 Mr. Song.flac"),
+            name: String::from("01 Mr"),
+            artist: String::from("AC/DC"),
+            title: String::from("Song: Live?"),
+            album: String::from(" Back in Black "),
+            album_artist: String::new(),
+            genre: String::new(),
+            year: Some(1980),
+            track_number: Some(7),
+            disc_number: None,
+            play_position: Duration::from_secs(0),
+            total_duration: Duration::from_secs(200),
+            start_time: None,
+            play_recorded: false,
+        }
+    }
+
+    #[test]
+    fn placeholders() {
+        let music = music();
+        assert_eq!(
+            render(
+                "{albumartist}/{year} - {album}/{track:02} {title}.{ext}",
+                &music
+            )
+            .unwrap(),
+            "AC_DC/1980 - Back in Black/07 Song_ Live_.flac"
+        );
+        assert_eq!(
+            render("{GENRE}/{disc}-{track}", &music).unwrap(),
+            "Unknown/0-7"
+        );
+        assert_eq!(render("{name}", &music).unwrap(), "01 Mr. Song");
+        assert_eq!(render("plain", &music).unwrap(), "plain");
+    }
+
+    #[test]
+    fn fallbacks() {
+        let music = Music {
+            album_artist: String::from("Various"),
+            title: String::from("  "),
+            year: None,
+            track_number: None,
+            ..music()
+        };
+        assert_eq!(
+            render("{albumartist} {year} {track:03} {title}", &music).unwrap(),
+            "Various Unknown 000 01 Mr. Song"
+        );
+        assert_eq!(render("{track}", &music).unwrap(), "0");
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        let music = music();
+        assert!(render("{artist", &music).is_err());
+        assert!(render("{composer}", &music).is_err());
+        assert!(render("/{artist}", &music).is_err());
+        assert!(render("../{artist}", &music).is_err());
+        assert!(render("{artist}/../../x", &music).is_err());
+    }
+
+    #[test]
+    fn sanitized_values() {
+        assert_eq!(sanitize("a/b\\c"), "a_b_c");
+        assert_eq!(sanitize("..hidden.. "), "hidden");
+        assert_eq!(sanitize("tab\there"), "tab_here");
+        assert_eq!(sanitize(".."), "");
+    }
+
+    #[test]
+    fn apply_skips_conflicts_and_removes_empty_folders() {
+        let dir =
+            std::env::temp_dir().join(format!("music_player_organize_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("old")).unwrap();
+        fs::write(dir.join("old/a.mp3"), b"a").unwrap();
+        fs::write(dir.join("b.mp3"), b"b").unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+
+        let moves = [
+            Move {
+                from: path("old/a.mp3"),
+                to: path("new/a.mp3"),
+                conflict: false,
+            },
+            Move {
+                from: path("b.mp3"),
+                to: path("new/a.mp3"),
+                conflict: true,
+            },
+        ];
+        let (done, errors) = apply(&moves, &path(""));
+        let moved = fs::read(dir.join("new/a.mp3")).unwrap();
+        let old_removed = !dir.join("old").exists();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(done.len(), 1);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(moved, b"a");
+        assert!(old_removed);
+    }
+}
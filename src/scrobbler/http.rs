@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+// Anything able to POST a request body, replaceable to talk to a mock server
+pub trait HttpClient: Send {
+    fn post(
+        &self,
+        url: &str,
+        headers: &[(&str, String)],
+        body: &str,
+    ) -> Result<HttpResponse, String>;
+}
+
+// http:// and https:// through ureq
+pub struct DefaultHttpClient;
+
+impl HttpClient for DefaultHttpClient {
+    fn post(
+        &self,
+        url: &str,
+        headers: &[(&str, String)],
+        body: &str,
+    ) -> Result<HttpResponse, String> {
+        let mut request = ureq::post(url).timeout(TIMEOUT);
+        for (name, value) in headers {
+            request = request.set(name, value);
+        }
+
+        // Error statuses are answers too, the caller decides whether to retry
+        let response = match request.send_string(body) {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(err) => return Err(err.to_string()),
+        };
+        let status = response.status();
+        let body = response.into_string().map_err(|err| err.to_string())?;
+
+        Ok(HttpResponse { status, body })
+    }
+}
+
+// A server answering one request, which is handed back as its request line, headers and body
+#[cfg(test)]
+pub fn mock_server(status: u16, response: &str) -> (String, std::sync::mpsc::Receiver<String>) {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let response = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        response.len(),
+        response
+    );
+    let (sender, receiver) = std::sync::mpsc::channel();
+
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut request = String::new();
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            request.push_str(&line);
+            if line == "\r\n" {
+                break;
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        request.push_str(&String::from_utf8(body).unwrap());
+        reader.get_mut().write_all(response.as_bytes()).unwrap();
+        sender.send(request).unwrap();
+    });
+
+    (url, receiver)
+}
@@ -0,0 +1,174 @@
+use crate::utils::md5::md5_hex;
+
+use super::http::HttpClient;
+use super::{check_response, json_error_code, Listen, Service, SubmitError};
+
+pub const DEFAULT_BASE_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+
+// Error codes that mean "try again later"
+const RETRY_ERRORS: &[u64] = &[11, 16, 29];
+
+pub struct LastFm {
+    pub base_url: String,
+    pub api_key: String,
+    pub api_secret: String,
+    pub session_key: String,
+}
+
+impl LastFm {
+    fn call(
+        &self,
+        client: &dyn HttpClient,
+        mut params: Vec<(String, String)>,
+    ) -> Result<(), SubmitError> {
+        params.push((String::from("api_key"), self.api_key.clone()));
+        params.push((String::from("sk"), self.session_key.clone()));
+
+        // api_sig is the md5 of every parameter sorted by name, followed by the secret
+        params.sort();
+        let mut signature = String::new();
+        for (name, value) in &params {
+            signature.push_str(name);
+            signature.push_str(value);
+        }
+        signature.push_str(&self.api_secret);
+        params.push((String::from("api_sig"), md5_hex(signature.as_bytes())));
+        params.push((String::from("format"), String::from("json")));
+
+        let body: Vec<String> = params
+            .iter()
+            .map(|(name, value)| format!("{}={}", url_encode(name), url_encode(value)))
+            .collect();
+        let headers = [(
+            "Content-Type",
+            String::from("application/x-www-form-urlencoded"),
+        )];
+        let response = check_response(client.post(&self.base_url, &headers, &body.join("&")))?;
+
+        match json_error_code(&response.body) {
+            Some(code) if RETRY_ERRORS.contains(&code) => {
+                Err(SubmitError::Unreachable(format!("Last.fm error {}", code)))
+            }
+            Some(code) => Err(SubmitError::Rejected(format!("Last.fm error {}", code))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Service for LastFm {
+    fn now_playing(&self, client: &dyn HttpClient, listen: &Listen) -> Result<(), SubmitError> {
+        let params = vec![
+            (
+                String::from("method"),
+                String::from("track.updateNowPlaying"),
+            ),
+            (String::from("artist"), listen.artist.clone()),
+            (String::from("track"), listen.title.clone()),
+            (String::from("album"), listen.album.clone()),
+            (String::from("duration"), listen.duration.to_string()),
+        ];
+        self.call(client, params)
+    }
+
+    fn scrobble(&self, client: &dyn HttpClient, listens: &[Listen]) -> Result<(), SubmitError> {
+        let mut params = vec![(String::from("method"), String::from("track.scrobble"))];
+        for (i, listen) in listens.iter().enumerate() {
+            params.push((format!("artist[{}]", i), listen.artist.clone()));
+            params.push((format!("track[{}]", i), listen.title.clone()));
+            params.push((format!("album[{}]", i), listen.album.clone()));
+            params.push((format!("duration[{}]", i), listen.duration.to_string()));
+            params.push((format!("timestamp[{}]", i), listen.timestamp.to_string()));
+        }
+        self.call(client, params)
+    }
+}
+
+fn url_encode(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::http::{mock_server, DefaultHttpClient};
+    use super::*;
+
+    fn lastfm(base_url: String) -> LastFm {
+        LastFm {
+            base_url,
+            api_key: String::from("key"),
+            api_secret: String::from("secret"),
+            session_key: String::from("session"),
+        }
+    }
+
+    fn listen() -> Listen {
+        Listen {
+            artist: String::from("A & B"),
+            title: String::from("Song"),
+            album: String::from("Alibi"),
+            duration: 200,
+            timestamp: 1_700_000_000,
+        }
+    }
+
+    fn body(request: &str) -> &str {
+        request.split_once("\r\n\r\n").unwrap().1
+    }
+
+    #[test]
+    fn scrobble_form() {
+        let (url, requests) = mock_server(200, "{\"scrobbles\":{}}");
+        let result = lastfm(url).scrobble(&DefaultHttpClient, &[listen()]);
+        assert!(result.is_ok());
+
+        let request = requests.recv().unwrap();
+        assert!(request.starts_with("POST / HTTP/1.1\r\n"));
+        assert!(request
+            .to_lowercase()
+            .contains("content-type: application/x-www-form-urlencoded\r\n"));
+        assert_eq!(
+            body(&request),
+            "album%5B0%5D=Alibi&api_key=key&artist%5B0%5D=A%20%26%20B&duration%5B0%5D=200\
+             &method=track.scrobble&sk=session&timestamp%5B0%5D=1700000000&track%5B0%5D=Song\
+             &api_sig=eec3fa6b9bdd1a2beeeacec5a48e7b54&format=json"
+        );
+    }
+
+    #[test]
+    fn now_playing_form() {
+        let (url, requests) = mock_server(200, "{\"nowplaying\":{}}");
+        let result = lastfm(url).now_playing(&DefaultHttpClient, &listen());
+        assert!(result.is_ok());
+
+        assert_eq!(
+            body(&requests.recv().unwrap()),
+            "album=Alibi&api_key=key&artist=A%20%26%20B&duration=200\
+             &method=track.updateNowPlaying&sk=session&track=Song\
+             &api_sig=8501dbe603e52e8c1ac3474950f19224&format=json"
+        );
+    }
+
+    #[test]
+    fn error_codes() {
+        let (url, _requests) = mock_server(200, "{\"error\": 9, \"message\": \"Invalid session\"}");
+        let result = lastfm(url).now_playing(&DefaultHttpClient, &listen());
+        assert!(matches!(result, Err(SubmitError::Rejected(_))));
+
+        let (url, _requests) = mock_server(200, "{\"error\": 16}");
+        let result = lastfm(url).now_playing(&DefaultHttpClient, &listen());
+        assert!(matches!(result, Err(SubmitError::Unreachable(_))));
+
+        let (url, _requests) = mock_server(503, "");
+        let result = lastfm(url).now_playing(&DefaultHttpClient, &listen());
+        assert!(matches!(result, Err(SubmitError::Unreachable(_))));
+    }
+}
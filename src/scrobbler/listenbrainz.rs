@@ -0,0 +1,150 @@
+use super::http::HttpClient;
+use super::{check_response, Listen, Service, SubmitError};
+
+pub const DEFAULT_BASE_URL: &str = "https://api.listenbrainz.org";
+
+pub struct ListenBrainz {
+    pub base_url: String,
+    pub token: String,
+}
+
+impl ListenBrainz {
+    fn submit(
+        &self,
+        client: &dyn HttpClient,
+        listen_type: &str,
+        payload: Vec<String>,
+    ) -> Result<(), SubmitError> {
+        let url = format!("{}/1/submit-listens", self.base_url.trim_end_matches('/'));
+        let body = format!(
+            "{{\"listen_type\":\"{}\",\"payload\":[{}]}}",
+            listen_type,
+            payload.join(",")
+        );
+        let headers = [
+            ("Authorization", format!("Token {}", self.token)),
+            ("Content-Type", String::from("application/json")),
+        ];
+        check_response(client.post(&url, &headers, &body))?;
+
+        Ok(())
+    }
+}
+
+impl Service for ListenBrainz {
+    fn now_playing(&self, client: &dyn HttpClient, listen: &Listen) -> Result<(), SubmitError> {
+        self.submit(
+            client,
+            "playing_now",
+            vec![format!("{{\"track_metadata\":{}}}", track_metadata(listen))],
+        )
+    }
+
+    fn scrobble(&self, client: &dyn HttpClient, listens: &[Listen]) -> Result<(), SubmitError> {
+        let payload = listens
+            .iter()
+            .map(|listen| {
+                format!(
+                    "{{\"listened_at\":{},\"track_metadata\":{}}}",
+                    listen.timestamp,
+                    track_metadata(listen)
+                )
+            })
+            .collect();
+        let listen_type = if listens.len() == 1 {
+            "single"
+        } else {
+            "import"
+        };
+        self.submit(client, listen_type, payload)
+    }
+}
+
+fn track_metadata(listen: &Listen) -> String {
+    format!(
+        "{{\"artist_name\":\"{}\",\"track_name\":\"{}\",\"release_name\":\"{}\",\"additional_info\":{{\"duration_ms\":{}}}}}",
+        json_escape(&listen.artist),
+        json_escape(&listen.title),
+        json_escape(&listen.album),
+        listen.duration * 1000
+    )
+}
+
+fn json_escape(value: &str) -> String {
+    let mut escaped = String::new();
+    for chr in value.chars() {
+        match chr {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::http::{mock_server, DefaultHttpClient};
+    use super::*;
+
+    fn listen(title: &str, timestamp: u64) -> Listen {
+        Listen {
+            artist: String::from("Artist"),
+            title: String::from(title),
+            album: String::from("Album"),
+            duration: 200,
+            timestamp,
+        }
+    }
+
+    fn submit(submission: impl FnOnce(&ListenBrainz) -> Result<(), SubmitError>) -> (bool, String) {
+        let (url, requests) = mock_server(200, "{\"status\":\"ok\"}");
+        let service = ListenBrainz {
+            base_url: format!("{}/", url),
+            token: String::from("token"),
+        };
+        let ok = submission(&service).is_ok();
+        (ok, requests.recv().unwrap())
+    }
+
+    #[test]
+    fn single_listen() {
+        let (ok, request) = submit(|service| {
+            service.scrobble(&DefaultHttpClient, &[listen("Say \"hi\"\n", 1_700_000_000)])
+        });
+        assert!(ok);
+        assert!(request.starts_with("POST /1/submit-listens HTTP/1.1\r\n"));
+        let lowercase = request.to_lowercase();
+        assert!(lowercase.contains("authorization: token token\r\n"));
+        assert!(lowercase.contains("content-type: application/json\r\n"));
+        assert!(request.ends_with(
+            "\r\n\r\n{\"listen_type\":\"single\",\"payload\":[{\"listened_at\":1700000000,\
+             \"track_metadata\":{\"artist_name\":\"Artist\",\"track_name\":\"Say \\\"hi\\\"\\n\",\
+             \"release_name\":\"Album\",\"additional_info\":{\"duration_ms\":200000}}}]}"
+        ));
+    }
+
+    #[test]
+    fn import_and_playing_now() {
+        let (ok, request) = submit(|service| {
+            service.scrobble(&DefaultHttpClient, &[listen("One", 1), listen("Two", 2)])
+        });
+        assert!(ok);
+        let body = request.split_once("\r\n\r\n").unwrap().1;
+        assert!(body.starts_with("{\"listen_type\":\"import\",\"payload\":[{\"listened_at\":1,"));
+        assert!(body.contains("},{\"listened_at\":2,"));
+
+        let (ok, request) =
+            submit(|service| service.now_playing(&DefaultHttpClient, &listen("One", 0)));
+        assert!(ok);
+        let body = request.split_once("\r\n\r\n").unwrap().1;
+        assert!(
+            body.starts_with("{\"listen_type\":\"playing_now\",\"payload\":[{\"track_metadata\":")
+        );
+        assert!(!body.contains("listened_at"));
+    }
+}
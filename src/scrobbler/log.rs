@@ -0,0 +1,66 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::music::Music;
+use crate::utils::split_path::expand_home;
+
+// Audioscrobbler portable player log (.scrobbler.log version 1.1):
+// artist, album, title, track number, length, L(istened) or S(kipped), timestamp, mbid
+pub struct ScrobbleLog {
+    path: PathBuf,
+}
+
+impl ScrobbleLog {
+    pub fn new(path: &str) -> ScrobbleLog {
+        ScrobbleLog {
+            path: expand_home(path),
+        }
+    }
+
+    // Append one entry, `listened` is false when the music was skipped
+    pub fn append(&self, music: &Music, listened: bool, timestamp: u64) -> Result<(), String> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|err| err.to_string())?;
+
+        let mut content = String::new();
+        if file.metadata().map_err(|err| err.to_string())?.len() == 0 {
+            content.push_str("#AUDIOSCROBBLER/1.1\n#TZ/UTC\n");
+            content.push_str(&format!(
+                "#CLIENT/music-player {}\n",
+                env!("CARGO_PKG_VERSION")
+            ));
+        }
+
+        let title = if music.title.is_empty() {
+            &music.name
+        } else {
+            &music.title
+        };
+        let track_number = match music.track_number {
+            Some(track_number) => track_number.to_string(),
+            None => String::new(),
+        };
+        content.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t\n",
+            clean_field(&music.artist),
+            clean_field(&music.album),
+            clean_field(title),
+            track_number,
+            music.total_duration.as_secs(),
+            if listened { "L" } else { "S" },
+            timestamp,
+        ));
+
+        file.write_all(content.as_bytes())
+            .map_err(|err| err.to_string())
+    }
+}
+
+// Tabs and line breaks would corrupt the log
+fn clean_field(field: &str) -> String {
+    field.replace(['\t', '\n', '\r'], " ")
+}
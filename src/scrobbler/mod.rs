@@ -0,0 +1,225 @@
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{config_dir, ScrobblerConfig};
+use crate::music::Music;
+
+use self::http::{DefaultHttpClient, HttpClient, HttpResponse};
+use self::lastfm::LastFm;
+use self::listenbrainz::ListenBrainz;
+use self::queue::Queue;
+
+pub mod http;
+pub mod lastfm;
+pub mod listenbrainz;
+pub mod log;
+mod queue;
+
+const QUEUE_FILE: &str = "scrobble_queue.yml";
+// How often queued listens are retried while the service is unreachable
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+// Largest batch Last.fm accepts in one request
+const BATCH_SIZE: usize = 50;
+// Services ignore anything shorter
+const MIN_DURATION: u64 = 30;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Listen {
+    pub artist: String,
+    pub title: String,
+    pub album: String,
+    // Seconds
+    pub duration: u64,
+    // Unix timestamp of when the music started playing
+    pub timestamp: u64,
+}
+
+impl Listen {
+    pub fn new(music: &Music, timestamp: u64) -> Listen {
+        let title = if music.title.is_empty() {
+            music.name.clone()
+        } else {
+            music.title.clone()
+        };
+
+        Listen {
+            artist: music.artist.clone(),
+            title,
+            album: music.album.clone(),
+            duration: music.total_duration.as_secs(),
+            timestamp,
+        }
+    }
+}
+
+pub enum SubmitError {
+    // Worth retrying later
+    Unreachable(String),
+    // The service refused the submission, retrying will not help
+    Rejected(String),
+}
+
+pub trait Service: Send {
+    fn now_playing(&self, client: &dyn HttpClient, listen: &Listen) -> Result<(), SubmitError>;
+    fn scrobble(&self, client: &dyn HttpClient, listens: &[Listen]) -> Result<(), SubmitError>;
+}
+
+fn check_response(response: Result<HttpResponse, String>) -> Result<HttpResponse, SubmitError> {
+    match response {
+        Ok(response) if (200..300).contains(&response.status) => Ok(response),
+        Ok(response) if response.status == 429 || response.status >= 500 => Err(
+            SubmitError::Unreachable(format!("HTTP {}", response.status)),
+        ),
+        Ok(response) => Err(SubmitError::Rejected(format!(
+            "HTTP {}: {}",
+            response.status,
+            response.body.trim()
+        ))),
+        Err(err) => Err(SubmitError::Unreachable(err)),
+    }
+}
+
+// The `"error": <code>` of a JSON error response
+fn json_error_code(body: &str) -> Option<u64> {
+    let index = body.find("\"error\"")?;
+    let rest = body[index + 7..]
+        .trim_start()
+        .strip_prefix(':')?
+        .trim_start();
+    let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse::<u64>().ok()
+}
+
+enum Message {
+    NowPlaying(Listen),
+    Listen(Listen),
+}
+
+// Submits in a background thread so a slow or offline service never blocks the UI
+pub struct Scrobbler {
+    sender: Sender<Message>,
+    errors: Receiver<String>,
+}
+
+impl Scrobbler {
+    pub fn new(config: &ScrobblerConfig) -> Scrobbler {
+        let service: Box<dyn Service> = match config {
+            ScrobblerConfig::Lastfm {
+                base_url,
+                api_key,
+                api_secret,
+                session_key,
+            } => Box::new(LastFm {
+                base_url: base_url
+                    .clone()
+                    .unwrap_or_else(|| String::from(lastfm::DEFAULT_BASE_URL)),
+                api_key: api_key.clone(),
+                api_secret: api_secret.clone(),
+                session_key: session_key.clone(),
+            }),
+            ScrobblerConfig::Listenbrainz { base_url, token } => Box::new(ListenBrainz {
+                base_url: base_url
+                    .clone()
+                    .unwrap_or_else(|| String::from(listenbrainz::DEFAULT_BASE_URL)),
+                token: token.clone(),
+            }),
+        };
+        let queue_path = config_dir().map(|mut path| {
+            path.push(QUEUE_FILE);
+            path
+        });
+
+        Scrobbler::with_client(service, Box::new(DefaultHttpClient), queue_path)
+    }
+
+    pub fn with_client(
+        service: Box<dyn Service>,
+        client: Box<dyn HttpClient>,
+        queue_path: Option<PathBuf>,
+    ) -> Scrobbler {
+        let (sender, receiver) = mpsc::channel();
+        let (error_sender, errors) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut queue = Queue::load(queue_path);
+            let mut report = |err: String| {
+                let _ = error_sender.send(err);
+            };
+            flush(&*service, &*client, &mut queue, &mut report);
+
+            loop {
+                match receiver.recv_timeout(RETRY_INTERVAL) {
+                    Ok(Message::NowPlaying(listen)) => {
+                        if let Err(SubmitError::Rejected(err)) =
+                            service.now_playing(&*client, &listen)
+                        {
+                            report(err);
+                        }
+                    }
+                    Ok(Message::Listen(listen)) => {
+                        queue.listens.push(listen);
+                        if let Err(err) = queue.save() {
+                            report(err);
+                        }
+                        if let Some(err) = flush(&*service, &*client, &mut queue, &mut report) {
+                            report(format!("Scrobble queued: {}", err));
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        flush(&*service, &*client, &mut queue, &mut report);
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+        });
+
+        Scrobbler { sender, errors }
+    }
+
+    pub fn now_playing(&self, music: &Music) {
+        if music.total_duration.as_secs() >= MIN_DURATION {
+            let _ = self.sender.send(Message::NowPlaying(Listen::new(music, 0)));
+        }
+    }
+
+    pub fn listen(&self, music: &Music, timestamp: u64) {
+        if music.total_duration.as_secs() >= MIN_DURATION {
+            let _ = self
+                .sender
+                .send(Message::Listen(Listen::new(music, timestamp)));
+        }
+    }
+
+    // The latest submission error, if any
+    pub fn poll_error(&self) -> Option<String> {
+        self.errors.try_iter().last()
+    }
+}
+
+// Submit queued listens in batches until the queue is empty or the service is unreachable,
+// returns why it was unreachable
+fn flush(
+    service: &dyn Service,
+    client: &dyn HttpClient,
+    queue: &mut Queue,
+    report: &mut dyn FnMut(String),
+) -> Option<String> {
+    while !queue.listens.is_empty() {
+        let batch_size = queue.listens.len().min(BATCH_SIZE);
+        match service.scrobble(client, &queue.listens[..batch_size]) {
+            Ok(_) => {}
+            Err(SubmitError::Unreachable(err)) => return Some(err),
+            Err(SubmitError::Rejected(err)) => report(err),
+        }
+        queue.listens.drain(..batch_size);
+        if let Err(err) = queue.save() {
+            report(err);
+        }
+    }
+
+    None
+}
@@ -0,0 +1,40 @@
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+use super::Listen;
+
+// Listens waiting to be submitted, kept on disk while the service is unreachable
+pub struct Queue {
+    path: Option<PathBuf>,
+    pub listens: Vec<Listen>,
+}
+
+impl Queue {
+    pub fn load(path: Option<PathBuf>) -> Queue {
+        let listens = match &path {
+            Some(path) => match File::open(path) {
+                Ok(file) => serde_yaml::from_reader(file).unwrap_or_default(),
+                Err(_) => Vec::new(),
+            },
+            None => Vec::new(),
+        };
+
+        Queue { path, listens }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        if let Some(path) = &self.path {
+            if self.listens.is_empty() {
+                if path.exists() {
+                    fs::remove_file(path).map_err(|err| err.to_string())?;
+                }
+            } else {
+                let content =
+                    serde_yaml::to_string(&self.listens).map_err(|err| err.to_string())?;
+                fs::write(path, content).map_err(|err| err.to_string())?;
+            }
+        }
+
+        Ok(())
+    }
+}
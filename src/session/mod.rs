@@ -0,0 +1,55 @@
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::PlayStyle;
+use crate::config::config_dir;
+
+const SESSION_FILE: &str = "session.yml";
+// Besides on exit, the session is saved this often in case the program does not get to exit
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+// What is restored on the next launch
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    #[serde(default)]
+    pub play_list: Vec<String>,
+    #[serde(default)]
+    pub playing_music: Option<String>,
+    // Milliseconds into the playing music
+    #[serde(default)]
+    pub position: u64,
+    #[serde(default = "default_volume")]
+    pub volume: f32,
+    #[serde(default)]
+    pub play_style: PlayStyle,
+}
+
+fn default_volume() -> f32 {
+    1.0
+}
+
+fn file_path() -> Option<PathBuf> {
+    config_dir().map(|mut path| {
+        path.push(SESSION_FILE);
+        path
+    })
+}
+
+impl Session {
+    pub fn load() -> Option<Session> {
+        let file = File::open(file_path()?).ok()?;
+        serde_yaml::from_reader(file).ok()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        if let Some(path) = file_path() {
+            let content = serde_yaml::to_string(self).map_err(|err| err.to_string())?;
+            fs::write(path, content).map_err(|err| err.to_string())?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,750 @@
+use std::iter::Peekable;
+use std::str::Chars;
+use std::time::Duration;
+
+use rand::prelude::SliceRandom;
+
+use crate::music::Music;
+use crate::stats::TrackStats;
+
+// What a smart playlist query can see of a track
+pub struct Track<'a> {
+    pub music: &'a Music,
+    pub rating: u8,
+    pub play_count: u32,
+    pub skip_count: u32,
+    // Unix timestamp of the last play
+    pub last_played: Option<u64>,
+}
+
+impl<'a> Track<'a> {
+    pub fn new(music: &'a Music, stats: &TrackStats) -> Track<'a> {
+        Track {
+            music,
+            rating: stats.rating,
+            play_count: stats.play_count,
+            skip_count: stats.skip_count,
+            last_played: stats.last_played,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Artist,
+    Title,
+    Album,
+    Genre,
+    Name,
+    Path,
+    Year,
+    Track,
+    Duration,
+    Rating,
+    Plays,
+    Skips,
+}
+
+impl Field {
+    fn parse(word: &str) -> Result<Field, String> {
+        match word.to_ascii_lowercase().as_ref() {
+            "artist" => Ok(Field::Artist),
+            "title" => Ok(Field::Title),
+            "album" => Ok(Field::Album),
+            "genre" => Ok(Field::Genre),
+            "name" => Ok(Field::Name),
+            "path" => Ok(Field::Path),
+            "year" => Ok(Field::Year),
+            "track" => Ok(Field::Track),
+            "duration" | "length" => Ok(Field::Duration),
+            "rating" => Ok(Field::Rating),
+            "plays" | "playcount" => Ok(Field::Plays),
+            "skips" | "skipcount" => Ok(Field::Skips),
+            _ => Err(format!("Unknown field '{}'", word)),
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            Field::Year
+                | Field::Track
+                | Field::Duration
+                | Field::Rating
+                | Field::Plays
+                | Field::Skips
+        )
+    }
+
+    fn text<'a>(&self, track: &'a Track) -> &'a str {
+        match self {
+            Field::Artist => &track.music.artist,
+            Field::Title => &track.music.title,
+            Field::Album => &track.music.album,
+            Field::Genre => &track.music.genre,
+            Field::Name => &track.music.name,
+            Field::Path => &track.music.path,
+            _ => "",
+        }
+    }
+
+    fn number(&self, track: &Track) -> Option<f64> {
+        match self {
+            Field::Year => track.music.year.map(|year| year as f64),
+            Field::Track => track.music.track_number.map(|number| number as f64),
+            Field::Duration => Some(track.music.total_duration.as_secs_f64()),
+            Field::Rating => Some(track.rating as f64),
+            Field::Plays => Some(track.play_count as f64),
+            Field::Skips => Some(track.skip_count as f64),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl Op {
+    fn compare<T: PartialOrd>(&self, left: T, right: T) -> bool {
+        match self {
+            Op::Eq => left == right,
+            Op::Ne => left != right,
+            Op::Gt => left > right,
+            Op::Ge => left >= right,
+            Op::Lt => left < right,
+            Op::Le => left <= right,
+        }
+    }
+}
+
+enum Value {
+    Text(String),
+    Number(f64),
+}
+
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Contains(Field, String),
+    Compare(Field, Op, Value),
+    // Played at least once within the given number of seconds
+    PlayedWithin(u64),
+    Played,
+}
+
+impl Expr {
+    fn matches(&self, track: &Track, now: u64) -> bool {
+        match self {
+            Expr::And(left, right) => left.matches(track, now) && right.matches(track, now),
+            Expr::Or(left, right) => left.matches(track, now) || right.matches(track, now),
+            Expr::Not(expr) => !expr.matches(track, now),
+            Expr::Contains(field, value) => match field.number(track) {
+                Some(number) => value.parse::<f64>() == Ok(number),
+                None => field.text(track).to_lowercase().contains(value),
+            },
+            Expr::Compare(field, op, Value::Number(value)) => match field.number(track) {
+                Some(number) => op.compare(number, *value),
+                None => false,
+            },
+            Expr::Compare(field, op, Value::Text(value)) => {
+                op.compare(field.text(track).to_lowercase().as_str(), value.as_str())
+            }
+            Expr::PlayedWithin(seconds) => match track.last_played {
+                Some(last_played) => now.saturating_sub(last_played) <= *seconds,
+                None => false,
+            },
+            Expr::Played => track.play_count > 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    // Quoted text, never treated as a keyword
+    Text(String),
+    Op(Op),
+    Colon,
+    Comma,
+    LParen,
+    RParen,
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars: Peekable<Chars> = query.chars().peekable();
+
+    while let Some(chr) = chars.next() {
+        match chr {
+            c if c.is_whitespace() => {}
+            ':' => tokens.push(Token::Colon),
+            ',' => tokens.push(Token::Comma),
+            '(' => tokens.push(Token::LParen),
+            ')' => tokens.push(Token::RParen),
+            '>' | '<' | '=' | '!' => {
+                let with_eq = chars.peek() == Some(&'=');
+                if with_eq {
+                    chars.next();
+                }
+                let op = match (chr, with_eq) {
+                    ('>', false) => Op::Gt,
+                    ('>', true) => Op::Ge,
+                    ('<', false) => Op::Lt,
+                    ('<', true) => Op::Le,
+                    ('=', _) => Op::Eq,
+                    ('!', true) => Op::Ne,
+                    _ => return Err(String::from("Expected '!='")),
+                };
+                tokens.push(Token::Op(op));
+            }
+            '"' | '\'' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some(c) if c == chr => break,
+                        Some(c) => text.push(c),
+                        None => return Err(String::from("Unclosed quote")),
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            _ => {
+                let mut word = String::from(chr);
+                while let Some(c) = chars.peek() {
+                    // A colon inside a number is part of a duration like `3:20`
+                    let in_number =
+                        *c == ':' && word.chars().all(|c| c.is_ascii_digit() || c == ':');
+                    if !in_number && (c.is_whitespace() || ":,()<>=!\"'".contains(*c)) {
+                        break;
+                    }
+                    word.push(*c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+// Parse `90`, `90s`, `3m`, `2h`, `1h30m` or `3:20` into seconds
+fn parse_duration(value: &str) -> Option<f64> {
+    if value.contains(':') {
+        let mut seconds = 0.0;
+        for part in value.split(':') {
+            seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+        }
+        return Some(seconds);
+    }
+
+    let mut seconds = 0.0;
+    let mut number = String::new();
+    for chr in value.chars() {
+        match chr {
+            '0'..='9' | '.' => number.push(chr),
+            'h' | 'm' | 's' | 'd' => {
+                let unit = match chr {
+                    'd' => 86400.0,
+                    'h' => 3600.0,
+                    'm' => 60.0,
+                    _ => 1.0,
+                };
+                seconds += number.parse::<f64>().ok()? * unit;
+                number.clear();
+            }
+            _ => return None,
+        }
+    }
+    if !number.is_empty() {
+        seconds += number.parse::<f64>().ok()?;
+    }
+
+    Some(seconds)
+}
+
+fn is_word(token: Option<&Token>, words: &[&str]) -> bool {
+    match token {
+        Some(Token::Word(word)) => words.contains(&word.to_ascii_lowercase().as_ref()),
+        _ => false,
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.position + offset)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn next_value(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Word(word)) | Some(Token::Text(word)) => Ok(word),
+            _ => Err(String::from("Expected a value")),
+        }
+    }
+
+    // Whether the next tokens start a modifier rather than a condition
+    fn at_modifier(&self) -> bool {
+        if is_word(
+            self.peek(),
+            &[
+                "shuffled", "shuffle", "random", "limit", "sort", "sorted", "max",
+            ],
+        ) {
+            return true;
+        }
+        matches!(self.peek(), Some(Token::Word(_)))
+            && is_word(self.peek_at(1), &["max", "songs", "tracks"])
+    }
+
+    fn at_condition(&self) -> bool {
+        match self.peek() {
+            Some(Token::LParen) => true,
+            Some(Token::Word(_)) => {
+                is_word(self.peek(), &["not", "played"])
+                    || matches!(self.peek_at(1), Some(Token::Colon) | Some(Token::Op(_)))
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_and()?;
+        while is_word(self.peek(), &["or"]) {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_factor()?;
+        loop {
+            if is_word(self.peek(), &["and"]) {
+                self.next();
+            } else if self.at_modifier() || !self.at_condition() {
+                break;
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_factor()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_factor(&mut self) -> Result<Expr, String> {
+        if is_word(self.peek(), &["not"]) {
+            self.next();
+            return Ok(Expr::Not(Box::new(self.parse_factor()?)));
+        }
+        if is_word(self.peek(), &["played"]) {
+            self.next();
+            if !is_word(self.peek(), &["in", "within"]) {
+                return Ok(Expr::Played);
+            }
+            self.next();
+            if is_word(self.peek(), &["the"]) {
+                self.next();
+            }
+            if is_word(self.peek(), &["last"]) {
+                self.next();
+            }
+            let amount = self.next_value()?;
+            let unit = if is_word(
+                self.peek(),
+                &["days", "day", "hours", "hour", "weeks", "week"],
+            ) {
+                self.next_value()?
+            } else {
+                String::from("days")
+            };
+            let amount = amount
+                .parse::<u64>()
+                .map_err(|_| format!("'{}' is not a number", amount))?;
+            let unit = match unit.to_ascii_lowercase().as_ref() {
+                "hours" | "hour" => 3600,
+                "weeks" | "week" => 7 * 86400,
+                _ => 86400,
+            };
+            return match amount.checked_mul(unit) {
+                Some(seconds) => Ok(Expr::PlayedWithin(seconds)),
+                None => Err(format!("'{}' is too long a time", amount)),
+            };
+        }
+
+        match self.next() {
+            Some(Token::LParen) => {
+                let expr = self.parse_or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => Err(String::from("Expected ')'")),
+                }
+            }
+            Some(Token::Word(word)) => {
+                let field = Field::parse(&word)?;
+                match self.next() {
+                    Some(Token::Colon) => {
+                        let value = self.next_value()?;
+                        Ok(Expr::Contains(field, value.to_lowercase()))
+                    }
+                    Some(Token::Op(op)) => {
+                        let value = self.next_value()?;
+                        if field.is_numeric() {
+                            let number = match field {
+                                Field::Duration => parse_duration(&value),
+                                _ => value.parse::<f64>().ok(),
+                            };
+                            match number {
+                                Some(number) => Ok(Expr::Compare(field, op, Value::Number(number))),
+                                None => Err(format!("'{}' is not a number", value)),
+                            }
+                        } else {
+                            Ok(Expr::Compare(field, op, Value::Text(value.to_lowercase())))
+                        }
+                    }
+                    _ => Err(format!("Expected ':' or a comparison after '{}'", word)),
+                }
+            }
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+            None => Err(String::from("Unexpected end of query")),
+        }
+    }
+
+    fn parse_modifier(&mut self, query: &mut Query) -> Result<(), String> {
+        let word = self.next_value()?;
+        match word.to_ascii_lowercase().as_ref() {
+            "shuffled" | "shuffle" | "random" => query.shuffle = true,
+            "limit" => {
+                let limit = self.next_value()?;
+                query.limit = Some(
+                    limit
+                        .parse::<usize>()
+                        .map_err(|_| format!("'{}' is not a number", limit))?,
+                );
+            }
+            "sort" | "sorted" => {
+                if is_word(self.peek(), &["by"]) {
+                    self.next();
+                }
+                let field = Field::parse(&self.next_value()?)?;
+                let mut descending = false;
+                if is_word(self.peek(), &["desc", "asc"]) {
+                    descending = self.next_value()?.eq_ignore_ascii_case("desc");
+                }
+                query.sort = Some((field, descending));
+            }
+            "max" => {
+                let value = self.next_value()?;
+                query.max_duration = Some(Self::max_duration(&value)?);
+            }
+            _ => {
+                let kind = self.next_value()?;
+                if kind.eq_ignore_ascii_case("max") {
+                    query.max_duration = Some(Self::max_duration(&word)?);
+                } else {
+                    query.limit = Some(
+                        word.parse::<usize>()
+                            .map_err(|_| format!("'{}' is not a number", word))?,
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn max_duration(value: &str) -> Result<Duration, String> {
+        match parse_duration(value).map(Duration::try_from_secs_f64) {
+            Some(Ok(duration)) => Ok(duration),
+            Some(Err(_)) => Err(format!("'{}' is too long a duration", value)),
+            None => Err(format!("'{}' is not a duration", value)),
+        }
+    }
+}
+
+// A saved query, e.g. `genre:ambient and not played in 30 days, shuffled, 2h max`
+pub struct Query {
+    filter: Option<Expr>,
+    shuffle: bool,
+    sort: Option<(Field, bool)>,
+    limit: Option<usize>,
+    max_duration: Option<Duration>,
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Result<Query, String> {
+        let mut parser = Parser {
+            tokens: tokenize(query)?,
+            position: 0,
+        };
+        let mut query = Query {
+            filter: None,
+            shuffle: false,
+            sort: None,
+            limit: None,
+            max_duration: None,
+        };
+
+        if !parser.at_modifier() && parser.peek() != Some(&Token::Comma) && parser.peek().is_some()
+        {
+            query.filter = Some(parser.parse_or()?);
+        }
+        while let Some(token) = parser.peek() {
+            if token == &Token::Comma {
+                parser.next();
+            } else {
+                parser.parse_modifier(&mut query)?;
+            }
+        }
+
+        Ok(query)
+    }
+
+    pub fn matches(&self, track: &Track, now: u64) -> bool {
+        match &self.filter {
+            Some(filter) => filter.matches(track, now),
+            None => true,
+        }
+    }
+
+    // Filter, order and trim the tracks into a play list
+    pub fn select(&self, tracks: Vec<Track>, now: u64) -> Vec<Music> {
+        let mut tracks: Vec<Track> = tracks
+            .into_iter()
+            .filter(|track| self.matches(track, now))
+            .collect();
+
+        if self.shuffle {
+            tracks.shuffle(&mut rand::thread_rng());
+        } else if let Some((field, descending)) = self.sort {
+            tracks.sort_by(|a, b| {
+                let ordering = match (field.number(a), field.number(b)) {
+                    (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal),
+                    _ => field
+                        .text(a)
+                        .to_lowercase()
+                        .cmp(&field.text(b).to_lowercase()),
+                };
+                if descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+        }
+
+        if let Some(limit) = self.limit {
+            tracks.truncate(limit);
+        }
+
+        let mut total_duration = Duration::from_secs(0);
+        let mut music_list = Vec::new();
+        for track in tracks {
+            if let Some(max_duration) = self.max_duration {
+                // Skip tracks that do not fit, a shorter one may still do
+                if total_duration + track.music.total_duration > max_duration {
+                    continue;
+                }
+            }
+            total_duration += track.music.total_duration;
+            music_list.push(track.music.clone());
+        }
+
+        music_list
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 100_000_000;
+
+    fn music(title: &str, artist: &str, genre: &str, seconds: u64) -> Music {
+        Music {
+            path: format!("/music/{}.mp3", title),
+            name: title.to_string(),
+            artist: artist.to_string(),
+            title: title.to_string(),
+            album: String::new(),
+            album_artist: String::new(),
+            genre: genre.to_string(),
+            year: Some(2001),
+            track_number: Some(1),
+            disc_number: None,
+            play_position: Duration::from_secs(0),
+            total_duration: Duration::from_secs(seconds),
+            start_time: None,
+            play_recorded: false,
+        }
+    }
+
+    fn track(music: &Music, rating: u8, last_played: Option<u64>) -> Track<'_> {
+        Track {
+            music,
+            rating,
+            play_count: last_played.is_some() as u32,
+            skip_count: 0,
+            last_played,
+        }
+    }
+
+    // Titles of the tracks the query selects, in order
+    fn select(query: &str, tracks: Vec<Track>) -> Vec<String> {
+        let query = Query::parse(query).unwrap_or_else(|err| panic!("{}: {}", query, err));
+        query
+            .select(tracks, NOW)
+            .into_iter()
+            .map(|music| music.title)
+            .collect()
+    }
+
+    fn parse_error(query: &str) -> String {
+        match Query::parse(query) {
+            Ok(_) => panic!("'{}' should not parse", query),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("90"), Some(90.0));
+        assert_eq!(parse_duration("90s"), Some(90.0));
+        assert_eq!(parse_duration("3m"), Some(180.0));
+        assert_eq!(parse_duration("1h30m"), Some(5400.0));
+        assert_eq!(parse_duration("2d"), Some(172800.0));
+        assert_eq!(parse_duration("3:20"), Some(200.0));
+        assert_eq!(parse_duration("1:00:05"), Some(3605.0));
+        assert_eq!(parse_duration("3x"), None);
+        assert_eq!(parse_duration("a:b"), None);
+    }
+
+    #[test]
+    fn tokens() {
+        let tokens = tokenize("rating>=4 and title:'a b' or plays != 2").unwrap();
+        assert_eq!(
+            tokens,
+            [
+                Token::Word(String::from("rating")),
+                Token::Op(Op::Ge),
+                Token::Word(String::from("4")),
+                Token::Word(String::from("and")),
+                Token::Word(String::from("title")),
+                Token::Colon,
+                Token::Text(String::from("a b")),
+                Token::Word(String::from("or")),
+                Token::Word(String::from("plays")),
+                Token::Op(Op::Ne),
+                Token::Word(String::from("2")),
+            ]
+        );
+        assert_eq!(tokenize("title:\"open").unwrap_err(), "Unclosed quote");
+        assert_eq!(tokenize("plays ! 2").unwrap_err(), "Expected '!='");
+    }
+
+    #[test]
+    fn filters() {
+        let a = music("a", "Eno", "Ambient", 300);
+        let b = music("b", "Aphex", "ambient", 100);
+        let c = music("c", "Eno", "Rock", 200);
+        let tracks = || vec![track(&a, 5, None), track(&b, 2, None), track(&c, 4, None)];
+
+        assert_eq!(select("genre:ambient", tracks()), ["a", "b"]);
+        assert_eq!(select("genre:ambient and not artist:eno", tracks()), ["b"]);
+        assert_eq!(select("rating>=4", tracks()), ["a", "c"]);
+        assert_eq!(select("duration<3m", tracks()), ["b"]);
+        assert_eq!(select("duration>=3:20", tracks()), ["a", "c"]);
+        assert_eq!(select("artist:aphex or rating=4", tracks()), ["b", "c"]);
+        // `and` binds tighter than `or`, parentheses change that
+        assert_eq!(
+            select("artist:aphex or artist:eno genre:rock", tracks()),
+            ["b", "c"]
+        );
+        assert_eq!(
+            select("(artist:aphex or artist:eno) genre:rock", tracks()),
+            ["c"]
+        );
+        assert_eq!(select("title:\"a\"", tracks()), ["a"]);
+    }
+
+    #[test]
+    fn played() {
+        let a = music("a", "", "", 60);
+        let b = music("b", "", "", 60);
+        let c = music("c", "", "", 60);
+        let tracks = || {
+            vec![
+                track(&a, 0, Some(NOW - 2 * 86400)),
+                track(&b, 0, Some(NOW - 40 * 86400)),
+                track(&c, 0, None),
+            ]
+        };
+
+        assert_eq!(select("played", tracks()), ["a", "b"]);
+        assert_eq!(select("played in 30 days", tracks()), ["a"]);
+        assert_eq!(select("played within the last 1 week", tracks()), ["a"]);
+        assert_eq!(select("not played in 30 days", tracks()), ["b", "c"]);
+        assert_eq!(select("played in 1 hour", tracks()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn modifiers() {
+        let a = music("a", "", "", 300);
+        let b = music("b", "", "", 100);
+        let c = music("c", "", "", 200);
+        let tracks = || vec![track(&a, 0, None), track(&b, 0, None), track(&c, 0, None)];
+
+        assert_eq!(select("sort by duration", tracks()), ["b", "c", "a"]);
+        assert_eq!(
+            select("sort by duration desc, limit 2", tracks()),
+            ["a", "c"]
+        );
+        assert_eq!(select("sorted title desc, 1 songs", tracks()), ["c"]);
+        // Tracks that do not fit are skipped, later ones may still fit
+        assert_eq!(select("5m max", tracks()), ["a"]);
+        assert_eq!(select("max 5m, sort by duration", tracks()), ["b", "c"]);
+        assert_eq!(select("shuffled", tracks()).len(), 3);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse_error("colour:red"), "Unknown field 'colour'");
+        assert_eq!(parse_error("(genre:rock"), "Expected ')'");
+        assert_eq!(parse_error("rating>four"), "'four' is not a number");
+        assert_eq!(
+            parse_error("genre"),
+            "Expected ':' or a comparison after 'genre'"
+        );
+        assert_eq!(parse_error("limit many"), "'many' is not a number");
+        assert_eq!(parse_error("max soon"), "'soon' is not a duration");
+        assert_eq!(
+            parse_error("played in 99999999999999 weeks"),
+            "'99999999999999' is too long a time"
+        );
+        let huge = format!("{}d", "9".repeat(400));
+        assert_eq!(
+            parse_error(&format!("max {}", huge)),
+            format!("'{}' is too long a duration", huge)
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::fs;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::config::SortOrder;
+use crate::file_ops::DirectoryItem;
+use crate::music::Music;
+use crate::utils::split_path::split_path_to_name;
+
+// Name in the `sort` command and the config of every order
+const ORDERS: &[(SortOrder, &str)] = &[
+    (SortOrder::Folders, "folders"),
+    (SortOrder::Name, "name"),
+    (SortOrder::Modified, "modified"),
+    (SortOrder::Size, "size"),
+    (SortOrder::Track, "track"),
+    (SortOrder::Duration, "duration"),
+];
+
+pub fn order_names() -> Vec<String> {
+    ORDERS.iter().map(|(_, name)| name.to_string()).collect()
+}
+
+pub fn order_name(order: SortOrder) -> &'static str {
+    ORDERS
+        .iter()
+        .find(|(known, _)| *known == order)
+        .map_or("", |(_, name)| name)
+}
+
+pub fn parse_order(name: &str) -> Option<SortOrder> {
+    ORDERS
+        .iter()
+        .find(|(_, known)| known.eq_ignore_ascii_case(name))
+        .map(|(order, _)| *order)
+}
+
+// Runs of digits compare as numbers, so `2` comes before `10`
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum NamePart {
+    // Digits without leading zeros, their count first
+    Number(usize, String),
+    Text(String),
+}
+
+fn natural_key(name: &str) -> Vec<NamePart> {
+    let mut parts = Vec::new();
+    let mut chars = name.chars().peekable();
+    while let Some(&chr) = chars.peek() {
+        let is_digit = chr.is_ascii_digit();
+        let mut part = String::new();
+        while let Some(&chr) = chars.peek() {
+            if chr.is_ascii_digit() != is_digit {
+                break;
+            }
+            part.push(chr);
+            chars.next();
+        }
+        if is_digit {
+            let digits = part.trim_start_matches('0').to_string();
+            parts.push(NamePart::Number(digits.len(), digits));
+        } else {
+            parts.push(NamePart::Text(part.to_lowercase()));
+        }
+    }
+
+    parts
+}
+
+// Disc, track and duration from the tags
+type TrackKeys = (Option<u16>, Option<u16>, Duration);
+
+pub struct Sorter {
+    pub order: SortOrder,
+    // Tags are slow to read, they are kept for the next time the folder is listed
+    tags: HashMap<String, TrackKeys>,
+}
+
+impl Sorter {
+    pub fn new(order: SortOrder) -> Sorter {
+        Sorter {
+            order,
+            tags: HashMap::new(),
+        }
+    }
+
+    pub fn sort(&mut self, items: &mut [DirectoryItem]) {
+        if self.order == SortOrder::Track || self.order == SortOrder::Duration {
+            for item in items.iter() {
+                if let DirectoryItem::File(path) = item {
+                    if !self.tags.contains_key(path) {
+                        let keys = match Music::new(path) {
+                            Ok(music) => {
+                                (music.disc_number, music.track_number, music.total_duration)
+                            }
+                            Err(_) => (None, None, Duration::ZERO),
+                        };
+                        self.tags.insert(path.clone(), keys);
+                    }
+                }
+            }
+        }
+
+        items.sort_by_cached_key(|item| self.key(item));
+    }
+
+    // Read the tags again, after they were edited
+    pub fn forget(&mut self, paths: &[String]) {
+        for path in paths {
+            self.tags.remove(path);
+        }
+    }
+
+    // Whether it comes after the folders, what it is sorted by, smallest first, and its name
+    fn key(&self, item: &DirectoryItem) -> (bool, i128, Vec<NamePart>) {
+        let path = item.path();
+        let is_file = matches!(item, DirectoryItem::File(_));
+        let metadata = || fs::metadata(path).ok();
+        let tags = || {
+            self.tags
+                .get(path)
+                .copied()
+                .unwrap_or((None, None, Duration::ZERO))
+        };
+
+        let primary = match self.order {
+            SortOrder::Folders | SortOrder::Name => 0,
+            // Newest first, folders too
+            SortOrder::Modified => metadata()
+                .and_then(|metadata| metadata.modified().ok())
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |time| -(time.as_millis() as i128)),
+            SortOrder::Size if is_file => {
+                metadata().map_or(0, |metadata| -(metadata.len() as i128))
+            }
+            // Files without a track number last
+            SortOrder::Track if is_file => match tags() {
+                (disc, Some(track), _) => ((disc.unwrap_or(0) as i128) << 16) + track as i128,
+                (_, None, _) => i128::MAX,
+            },
+            SortOrder::Duration if is_file => -(tags().2.as_millis() as i128),
+            SortOrder::Size | SortOrder::Track | SortOrder::Duration => 0,
+        };
+
+        (
+            is_file && self.order != SortOrder::Name,
+            primary,
+            natural_key(split_path_to_name(path)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(order: SortOrder, items: &[DirectoryItem]) -> Vec<String> {
+        let mut items = items.to_vec();
+        Sorter::new(order).sort(&mut items);
+        items
+            .iter()
+            .map(|item| split_path_to_name(item.path()).to_string())
+            .collect()
+    }
+
+    fn files(names: &[&str]) -> Vec<DirectoryItem> {
+        names
+            .iter()
+            .map(|name| DirectoryItem::File(format!("/music/{}", name)))
+            .collect()
+    }
+
+    #[test]
+    fn numbers_sort_by_value() {
+        let items = files(&["10.mp3", "2.mp3", "1.mp3"]);
+        assert_eq!(
+            sorted(SortOrder::Name, &items),
+            ["1.mp3", "2.mp3", "10.mp3"]
+        );
+        let items = files(&["track 10", "track 9", "track 009b"]);
+        assert_eq!(
+            sorted(SortOrder::Name, &items),
+            ["track 9", "track 009b", "track 10"]
+        );
+    }
+
+    #[test]
+    fn leading_zeros_and_case_are_ignored() {
+        assert!(natural_key("007") == natural_key("7"));
+        assert!(natural_key("Beta") == natural_key("beta"));
+        assert!(natural_key("alpha") < natural_key("Beta"));
+        assert!(natural_key("a2") < natural_key("a10"));
+    }
+
+    #[test]
+    fn folders_come_first_except_by_name() {
+        let mut items = files(&["b.mp3", "d.mp3"]);
+        items.push(DirectoryItem::Directory(String::from("/music/c")));
+        items.push(DirectoryItem::Directory(String::from("/music/a")));
+        assert_eq!(
+            sorted(SortOrder::Folders, &items),
+            ["a", "c", "b.mp3", "d.mp3"]
+        );
+        assert_eq!(
+            sorted(SortOrder::Name, &items),
+            ["a", "b.mp3", "c", "d.mp3"]
+        );
+    }
+
+    #[test]
+    fn order_names_are_parsed_in_any_case() {
+        assert!(parse_order("Track") == Some(SortOrder::Track));
+        assert!(parse_order("nothing").is_none());
+        assert_eq!(order_name(SortOrder::Duration), "duration");
+    }
+}
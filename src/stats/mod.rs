@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{config_dir, ResumeFilesConfig};
+use crate::music::Music;
+use crate::utils::split_path::expand_home;
+use crate::utils::timestamp::now_timestamp;
+
+const STATS_FILE: &str = "stats.yml";
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TrackStats {
+    #[serde(default)]
+    pub play_count: u32,
+    #[serde(default)]
+    pub skip_count: u32,
+    // Unix timestamp of the last completed play
+    #[serde(default)]
+    pub last_played: Option<u64>,
+    // 0 - 5 stars
+    #[serde(default)]
+    pub rating: u8,
+    // Where a long file was left, until it is finished
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume: Option<ResumePosition>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub finished: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+// In milliseconds
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ResumePosition {
+    pub position: u64,
+    pub duration: u64,
+}
+
+impl TrackStats {
+    // How much of a long file was heard, from 0 to 100
+    pub fn progress(&self) -> Option<u64> {
+        if self.finished {
+            return Some(100);
+        }
+        self.resume
+            .filter(|resume| resume.duration > 0)
+            .map(|resume| (resume.position * 100 / resume.duration).min(99))
+    }
+}
+
+// Which files continue where they were left
+pub struct ResumeFiles {
+    min_duration: Option<Duration>,
+    folders: Vec<PathBuf>,
+}
+
+impl ResumeFiles {
+    pub fn new(config: &ResumeFilesConfig) -> ResumeFiles {
+        ResumeFiles {
+            min_duration: config
+                .min_minutes
+                .map(|minutes| Duration::from_secs(minutes * 60)),
+            folders: config
+                .folders
+                .iter()
+                .map(|folder| expand_home(folder))
+                .collect(),
+        }
+    }
+
+    pub fn applies(&self, music: &Music) -> bool {
+        self.min_duration
+            .is_some_and(|min_duration| music.total_duration >= min_duration)
+            || self
+                .folders
+                .iter()
+                .any(|folder| Path::new(&music.path).starts_with(folder))
+    }
+}
+
+// Play statistics of every file, keyed by path
+pub struct Stats {
+    file_path: Option<PathBuf>,
+    tracks: HashMap<String, TrackStats>,
+    empty: TrackStats,
+}
+
+impl Stats {
+    pub fn load() -> Stats {
+        let file_path = config_dir().map(|mut path| {
+            path.push(STATS_FILE);
+            path
+        });
+        let tracks = match &file_path {
+            Some(path) => match File::open(path) {
+                Ok(file) => serde_yaml::from_reader(file).unwrap_or_default(),
+                Err(_) => HashMap::new(),
+            },
+            None => HashMap::new(),
+        };
+
+        Stats {
+            file_path,
+            tracks,
+            empty: TrackStats::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        if let Some(path) = &self.file_path {
+            let content = serde_yaml::to_string(&self.tracks).map_err(|err| err.to_string())?;
+            fs::write(path, content).map_err(|err| err.to_string())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, path: &str) -> &TrackStats {
+        self.tracks.get(path).unwrap_or(&self.empty)
+    }
+
+    pub fn record_play(&mut self, path: &str) -> Result<(), String> {
+        let stats = self.tracks.entry(path.to_string()).or_default();
+        stats.play_count += 1;
+        stats.last_played = Some(now_timestamp());
+        self.save()
+    }
+
+    pub fn record_skip(&mut self, path: &str) -> Result<(), String> {
+        self.tracks.entry(path.to_string()).or_default().skip_count += 1;
+        self.save()
+    }
+
+    // Keep the statistics of files that were moved
+    pub fn rename(&mut self, renamed: &[(String, String)]) -> Result<(), String> {
+        for (from, to) in renamed {
+            if let Some(stats) = self.tracks.remove(from) {
+                self.tracks.insert(to.clone(), stats);
+            }
+        }
+        self.save()
+    }
+
+    // Forget the statistics of deleted files
+    pub fn remove(&mut self, paths: &[String]) -> Result<(), String> {
+        for path in paths {
+            self.tracks.remove(path);
+        }
+        self.save()
+    }
+
+    pub fn set_resume(&mut self, path: &str, resume: ResumePosition) -> Result<(), String> {
+        let stats = self.tracks.entry(path.to_string()).or_default();
+        stats.resume = Some(resume);
+        stats.finished = false;
+        self.save()
+    }
+
+    pub fn set_finished(&mut self, paths: &[String]) -> Result<(), String> {
+        for path in paths {
+            let stats = self.tracks.entry(path.to_string()).or_default();
+            stats.resume = None;
+            stats.finished = true;
+        }
+        self.save()
+    }
+
+    pub fn set_rating(&mut self, path: &str, rating: u8) -> Result<(), String> {
+        self.tracks.entry(path.to_string()).or_default().rating = rating;
+        self.save()
+    }
+}
@@ -0,0 +1,182 @@
+use crate::file_ops::TagChanges;
+use crate::music::Music;
+
+pub const TAG_FIELDS: [&str; 5] = ["artist", "title", "album", "track", "genre"];
+
+// State of the tag edit form
+pub struct TagEditor {
+    pub paths: Vec<String>,
+    pub values: Vec<String>,
+    // Only edited fields are written back
+    pub edited: Vec<bool>,
+    // Fields whose value differs between the files being edited
+    pub mixed: Vec<bool>,
+    pub selected: usize,
+}
+
+impl TagEditor {
+    pub fn new(paths: Vec<String>) -> TagEditor {
+        let mut values: Vec<Option<String>> = vec![None; TAG_FIELDS.len()];
+        let mut mixed = vec![false; TAG_FIELDS.len()];
+
+        for path in &paths {
+            let music = match Music::new(path) {
+                Ok(music) => music,
+                Err(_) => continue,
+            };
+            let track_number = match music.track_number {
+                Some(track_number) => track_number.to_string(),
+                None => String::new(),
+            };
+            let current = [
+                music.artist,
+                music.title,
+                music.album,
+                track_number,
+                music.genre,
+            ];
+            for (i, value) in current.into_iter().enumerate() {
+                match &values[i] {
+                    Some(existing) if *existing != value => mixed[i] = true,
+                    Some(_) => {}
+                    None => values[i] = Some(value),
+                }
+            }
+        }
+
+        let values = values
+            .into_iter()
+            .zip(&mixed)
+            .map(|(value, mixed)| if *mixed { None } else { value })
+            .map(Option::unwrap_or_default)
+            .collect();
+
+        TagEditor {
+            paths,
+            values,
+            edited: vec![false; TAG_FIELDS.len()],
+            mixed,
+            selected: 0,
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % TAG_FIELDS.len();
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = (self.selected + TAG_FIELDS.len() - 1) % TAG_FIELDS.len();
+    }
+
+    pub fn push_char(&mut self, chr: char) {
+        self.values[self.selected].push(chr);
+        self.edited[self.selected] = true;
+    }
+
+    pub fn pop_char(&mut self) {
+        self.values[self.selected].pop();
+        self.edited[self.selected] = true;
+    }
+
+    pub fn changes(&self) -> Result<TagChanges, String> {
+        let mut changes = TagChanges::default();
+        for (i, field) in TAG_FIELDS.iter().enumerate() {
+            if self.edited[i] {
+                set_change(&mut changes, field, &self.values[i])?;
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+fn set_change(changes: &mut TagChanges, field: &str, value: &str) -> Result<(), String> {
+    let value = value.trim().trim_matches('"').to_string();
+    match field {
+        "artist" => changes.artist = Some(value),
+        "title" => changes.title = Some(value),
+        "album" => changes.album = Some(value),
+        "genre" => changes.genre = Some(value),
+        // Left empty to remove the track number
+        "track" if value.is_empty() => changes.track_number = Some(None),
+        "track" => match value.parse::<u16>() {
+            Ok(track_number) => changes.track_number = Some(Some(track_number)),
+            Err(_) => return Err(format!("'{}' is not a track number", value)),
+        },
+        _ => return Err(format!("Unknown tag '{}'", field)),
+    }
+
+    Ok(())
+}
+
+// Parse `artist=Some Artist title=Some Title`, a value runs until the next `field=`
+pub fn parse_assignments(args: &str) -> Result<TagChanges, String> {
+    let mut changes = TagChanges::default();
+    let mut field: Option<String> = None;
+    let mut value: Vec<&str> = Vec::new();
+
+    for word in args.split_whitespace() {
+        if let Some((name, rest)) = word.split_once('=') {
+            if TAG_FIELDS.contains(&name.to_ascii_lowercase().as_ref()) {
+                if let Some(field) = field {
+                    set_change(&mut changes, &field, &value.join(" "))?;
+                }
+                field = Some(name.to_ascii_lowercase());
+                value = vec![rest];
+                continue;
+            }
+        }
+        match field {
+            Some(_) => value.push(word),
+            None => return Err(format!("Expected field=value, got '{}'", word)),
+        }
+    }
+    match field {
+        Some(field) => set_change(&mut changes, &field, &value.join(" "))?,
+        None => return Err(String::from("Usage: tag artist=... title=...")),
+    }
+
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_run_until_the_next_field() {
+        let changes = parse_assignments("artist=The Band TITLE=\"A Song\" track=7").unwrap();
+        assert_eq!(changes.artist.as_deref(), Some("The Band"));
+        assert_eq!(changes.title.as_deref(), Some("A Song"));
+        assert_eq!(changes.track_number, Some(Some(7)));
+        assert!(changes.album.is_none() && changes.genre.is_none());
+    }
+
+    #[test]
+    fn unknown_fields_are_part_of_the_value() {
+        let changes = parse_assignments("title=x=y album=").unwrap();
+        assert_eq!(changes.title.as_deref(), Some("x=y"));
+        assert_eq!(changes.album.as_deref(), Some(""));
+    }
+
+    #[test]
+    fn invalid_assignments() {
+        assert!(parse_assignments("").is_err());
+        assert!(parse_assignments("The Band").is_err());
+        assert!(parse_assignments("track=seven").is_err());
+        assert!(parse_assignments("track=70000").is_err());
+    }
+
+    #[test]
+    fn an_empty_track_removes_it() {
+        assert_eq!(
+            parse_assignments("track=").unwrap().track_number,
+            Some(None)
+        );
+
+        let mut changes = TagChanges::default();
+        set_change(&mut changes, "track", " ").unwrap();
+        assert_eq!(changes.track_number, Some(None));
+        assert!(set_change(&mut changes, "composer", "x").is_err());
+    }
+}
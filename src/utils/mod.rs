@@ -1 +1,3 @@
-pub mod split_path;
\ No newline at end of file
+pub mod md5;
+pub mod split_path;
+pub mod timestamp;
@@ -1,3 +1,16 @@
+use std::path::PathBuf;
+
+// `~/` at the start stands for the home folder
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(mut home)) => {
+            home.push(rest);
+            home
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
 pub fn split_path_to_name(path: &str) -> &str {
     let p = std::path::Path::new(path);
     p.file_name().unwrap().to_str().unwrap()
@@ -51,6 +51,7 @@ pub struct Theme {
     pub play_music_list_artist_color: Color,
     pub play_music_list_album_color: Color,
     pub play_music_list_header_color: Color,
+    pub play_music_list_stats_color: Color,
     pub playing_music_border_color: Color,
     pub playing_music_name_color: Color,
     pub volume_icon_color: Color,
@@ -58,11 +59,17 @@ pub struct Theme {
     pub gauge_color: Color,
     pub gauge_border_color: Color,
     pub gauge_label_color: Color,
+    pub list_marked_color: Color,
+    pub lyrics_color: Color,
+    pub lyrics_current_color: Color,
+    pub spectrum_color: Color,
+    pub oscilloscope_color: Color,
+    pub waveform_unplayed_color: Color,
 }
 
 impl Theme {
     #[rustfmt::skip]
-    pub fn new(init_theme: InitTheme) -> Theme {
+    pub fn new(init_theme: &InitTheme) -> Theme {
         Theme {
             list_title_color: parse_hex_color(&init_theme.list_title_color),
             list_title_page_color: parse_hex_color(&init_theme.list_title_page_color),
@@ -89,6 +96,7 @@ impl Theme {
             play_music_list_artist_color: parse_hex_color(&init_theme.play_music_list_artist_color),
             play_music_list_album_color: parse_hex_color(&init_theme.play_music_list_album_color),
             play_music_list_header_color: parse_hex_color(&init_theme.play_music_list_header_color),
+            play_music_list_stats_color: parse_hex_color(&init_theme.play_music_list_stats_color),
             playing_music_border_color: parse_hex_color(&init_theme.playing_music_border_color),
             playing_music_name_color: parse_hex_color(&init_theme.playing_music_name_color),
             volume_icon_color: parse_hex_color(&init_theme.volume_icon_color),
@@ -96,6 +104,12 @@ impl Theme {
             gauge_color: parse_hex_color(&init_theme.gauge_color),
             gauge_border_color: parse_hex_color(&init_theme.gauge_border_color),
             gauge_label_color: parse_hex_color(&init_theme.gauge_label_color),
+            list_marked_color: parse_hex_color(&init_theme.list_marked_color),
+            lyrics_color: parse_hex_color(&init_theme.lyrics_color),
+            lyrics_current_color: parse_hex_color(&init_theme.lyrics_current_color),
+            spectrum_color: parse_hex_color(&init_theme.spectrum_color),
+            oscilloscope_color: parse_hex_color(&init_theme.oscilloscope_color),
+            waveform_unplayed_color: parse_hex_color(&init_theme.waveform_unplayed_color),
         }
     }
 }
@@ -0,0 +1,87 @@
+use tui::backend::Backend;
+use tui::buffer::Buffer;
+use tui::layout::{Alignment, Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Style};
+use tui::text::Span;
+use tui::widgets::{Block, BorderType, Borders, Widget};
+use tui::Frame;
+
+use crate::cover::graphics::Protocol;
+use crate::cover::Image;
+
+use super::color::Theme;
+
+// Room for the cover on the left of `area`, about square and at most 2/5 of the width
+pub fn split_cover_area(area: Rect) -> (Rect, Rect) {
+    let width = (area.height.saturating_sub(2) * 2 + 2).min(area.width * 2 / 5);
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(width), Constraint::Min(0)])
+        .split(area);
+
+    (chunks[0], chunks[1])
+}
+
+// Returns the space left empty for kitty or sixel graphics
+pub fn draw_cover<B: Backend>(
+    frame: &mut Frame<B>,
+    area: Rect,
+    theme: &Theme,
+    image: &Image,
+    protocol: Protocol,
+) -> Option<Rect> {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(Span::styled(
+            " Cover ",
+            Style::default().fg(theme.play_music_list_title_color),
+        ))
+        .title_alignment(Alignment::Center)
+        .style(Style::default().fg(theme.play_music_list_border_color));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    if inner.width == 0 || inner.height == 0 {
+        return None;
+    }
+
+    match protocol {
+        Protocol::Blocks => {
+            frame.render_widget(HalfBlocks { image }, inner);
+            None
+        }
+        _ => Some(inner),
+    }
+}
+
+// The upper half block takes the top pixel as foreground and the bottom one as background
+struct HalfBlocks<'a> {
+    image: &'a Image,
+}
+
+impl<'a> Widget for HalfBlocks<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let (width, height) = self
+            .image
+            .fit(area.width as usize, area.height as usize * 2);
+        let image = self.image.resize(width, height);
+        let rows = height.div_ceil(2);
+        let x = area.x + (area.width - width as u16) / 2;
+        let y = area.y + (area.height - rows as u16) / 2;
+
+        let color = |pixel: [u8; 3]| Color::Rgb(pixel[0], pixel[1], pixel[2]);
+        for row in 0..rows {
+            for column in 0..width {
+                let top = image.pixels[row * 2 * width + column];
+                let bottom = match image.pixels.get((row * 2 + 1) * width + column) {
+                    Some(pixel) if row * 2 + 1 < height => color(*pixel),
+                    _ => Color::Reset,
+                };
+                buf.get_mut(x + column as u16, y + row as u16)
+                    .set_symbol("▀")
+                    .set_fg(color(top))
+                    .set_bg(bottom);
+            }
+        }
+    }
+}
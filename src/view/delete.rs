@@ -0,0 +1,63 @@
+use tui::backend::Backend;
+use tui::layout::{Alignment, Rect};
+use tui::style::{Color, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, BorderType, Borders, Clear, Paragraph};
+use tui::Frame;
+
+use crate::app::DeletePreview;
+use crate::keymap::{Action, Keymap};
+
+use super::color::Theme;
+
+// The files `keep` or `delete` are about to remove, nothing is deleted before [Enter]
+pub fn draw_delete_preview<B: Backend>(
+    frame: &mut Frame<B>,
+    area: Rect,
+    theme: &Theme,
+    preview: &DeletePreview,
+    music_database: &str,
+    keymap: &Keymap,
+) {
+    let lines: Vec<Spans> = preview
+        .paths
+        .iter()
+        .skip(preview.scroll)
+        .map(|path| {
+            let relative = path
+                .strip_prefix(music_database)
+                .unwrap_or(path)
+                .trim_start_matches('/');
+            Spans::from(Span::styled(
+                format!(" - {}", relative),
+                Style::default().fg(Color::LightRed),
+            ))
+        })
+        .collect();
+
+    let title = vec![
+        Span::styled(
+            format!(" Delete {} files ", preview.paths.len()),
+            Style::default().fg(theme.play_music_list_title_color),
+        ),
+        Span::styled(" | ", Style::default().fg(Color::Yellow)),
+        Span::styled(
+            format!(
+                " [{}] delete [{}] cancel ",
+                keymap.key_of(Action::Confirm),
+                keymap.key_of(Action::Cancel)
+            ),
+            Style::default().fg(theme.usage_color_right),
+        ),
+    ];
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(title)
+        .title_alignment(Alignment::Center)
+        .style(Style::default().fg(theme.play_music_list_border_color));
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
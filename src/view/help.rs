@@ -0,0 +1,95 @@
+use tui::backend::Backend;
+use tui::layout::{Alignment, Rect};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, BorderType, Borders, Clear, Paragraph};
+use tui::Frame;
+
+use crate::keymap::{Action, Keymap};
+
+use super::color::Theme;
+
+// Every key binding and every command, over the whole window
+pub fn draw_help<B: Backend>(
+    frame: &mut Frame<B>,
+    area: Rect,
+    theme: &Theme,
+    keymap: &Keymap,
+    commands: &[(String, &str)],
+    scroll: usize,
+) {
+    // Commands have long descriptions, leave only a margin
+    let width = area.width.saturating_sub(8).max(40).min(area.width);
+    let height = area.height.saturating_sub(4).max(10).min(area.height);
+    let rect = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+
+    let rows: Vec<(String, &str)> = keymap
+        .help()
+        .into_iter()
+        .map(|(description, keys)| (keys, description))
+        .collect();
+    let mut lines = section(theme, "Keys", &rows);
+    lines.push(Spans::default());
+    lines.extend(section(theme, "Commands (after :)", commands));
+
+    // Stop once the last line is at the bottom
+    let visible = height.saturating_sub(2) as usize;
+    let scroll = scroll.min(lines.len().saturating_sub(visible));
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(vec![
+            Span::styled(
+                " Help ",
+                Style::default().fg(theme.play_music_list_title_color),
+            ),
+            Span::styled(" | ", Style::default().fg(Color::Yellow)),
+            Span::styled(
+                format!(
+                    " [{}/{}] scroll [{}] close ",
+                    keymap.key_of(Action::DialogDown),
+                    keymap.key_of(Action::DialogUp),
+                    keymap.key_of(Action::Cancel)
+                ),
+                Style::default().fg(theme.usage_color_right),
+            ),
+        ])
+        .title_alignment(Alignment::Center)
+        .style(Style::default().fg(theme.play_music_list_border_color));
+
+    frame.render_widget(Clear, rect);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .scroll((scroll as u16, 0)),
+        rect,
+    );
+}
+
+// A title, then what to type and what it does in two columns
+fn section<'a>(theme: &Theme, title: &'a str, rows: &[(String, &'a str)]) -> Vec<Spans<'a>> {
+    let width = rows.iter().map(|(keys, _)| keys.chars().count()).max();
+    let mut lines = vec![Spans::from(Span::styled(
+        format!(" {}", title),
+        Style::default()
+            .fg(theme.play_music_list_title_color)
+            .add_modifier(Modifier::BOLD),
+    ))];
+    for (keys, description) in rows {
+        lines.push(Spans::from(vec![
+            Span::styled(
+                format!("   {:<1$}  ", keys, width.unwrap_or(0)),
+                Style::default().fg(theme.usage_color_right),
+            ),
+            Span::styled(*description, Style::default().fg(theme.usage_color_left)),
+        ]));
+    }
+
+    lines
+}
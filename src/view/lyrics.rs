@@ -0,0 +1,85 @@
+use tui::backend::Backend;
+use tui::layout::{Alignment, Rect};
+use tui::style::{Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, BorderType, Borders, Paragraph, Wrap};
+use tui::Frame;
+
+use crate::lyrics::LyricsPanel;
+use crate::music::Music;
+
+use super::color::Theme;
+
+pub fn draw_lyrics<B: Backend>(
+    frame: &mut Frame<B>,
+    area: Rect,
+    theme: &Theme,
+    panel: &LyricsPanel,
+    music: &Music,
+) {
+    let mut title = vec![Span::styled(
+        " Lyrics ",
+        Style::default().fg(theme.play_music_list_title_color),
+    )];
+    if panel.offset != 0 {
+        title.push(Span::styled(
+            format!("{:+.1}s ", panel.offset as f32 / 1000.0),
+            Style::default().fg(theme.play_music_list_duration_color),
+        ));
+    }
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(title)
+        .title_alignment(Alignment::Center)
+        .style(Style::default().fg(theme.play_music_list_border_color));
+    let height = block.inner(area).height as usize;
+
+    let lyrics = match panel.lyrics() {
+        Some(lyrics) => lyrics,
+        None => {
+            let paragraph = Paragraph::new(Span::styled(
+                "No lyrics found",
+                Style::default().fg(theme.lyrics_color),
+            ))
+            .alignment(Alignment::Center)
+            .block(block);
+            frame.render_widget(paragraph, area);
+            return;
+        }
+    };
+
+    // Keep the current line in the middle, lyrics without times scroll along with the music
+    let current = lyrics.current_line(music.play_position, panel.offset);
+    let top = if lyrics.is_synced() {
+        current.unwrap_or(0).saturating_sub(height / 2)
+    } else {
+        let progress = music.play_position.as_secs_f32() / music.total_duration.as_secs_f32();
+        let scrollable = lyrics.lines.len().saturating_sub(height);
+        (scrollable as f32 * progress.clamp(0.0, 1.0)) as usize
+    };
+
+    let lines: Vec<Spans> = lyrics
+        .lines
+        .iter()
+        .enumerate()
+        .skip(top)
+        .take(height)
+        .map(|(i, line)| {
+            let style = if Some(i) == current {
+                Style::default()
+                    .fg(theme.lyrics_current_color)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(theme.lyrics_color)
+            };
+            Spans::from(Span::styled(line.text.as_str(), style))
+        })
+        .collect();
+
+    let paragraph = Paragraph::new(lines)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true })
+        .block(block);
+    frame.render_widget(paragraph, area);
+}
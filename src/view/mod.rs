@@ -1,43 +1,112 @@
+use std::io;
+
 use exitfailure::ExitFailure;
 use tui::layout::{Constraint, Direction, Layout};
 
-use crate::app::App;
+use crate::app::{App, Focus, Mode, MouseAreas};
+use crate::commands;
 use crate::config::InitTheme;
+use crate::file_ops::DirectoryItem;
+use crate::sort;
 
 use self::color::Theme;
-use self::music_list::draw_music_list;
-use self::play_music_list::draw_play_music_list;
-use self::playing_music::draw_playing_music;
+use self::cover::{draw_cover, split_cover_area};
+use self::delete::draw_delete_preview;
+use self::help::draw_help;
+use self::lyrics::draw_lyrics;
+use self::music_list::{draw_music_list, MusicList, Prompt};
+use self::organize::draw_organize_preview;
+use self::play_music_list::{draw_home_page, draw_play_music_list, PlayList};
+use self::playing_music::{draw_playing_music, draw_seek_bar};
+use self::tag_editor::draw_tag_editor;
+use self::visualizer::draw_visualizer;
 pub mod color;
+mod cover;
+mod delete;
 mod display;
+mod help;
+mod lyrics;
 mod music_list;
+mod organize;
 mod play_music_list;
 mod playing_music;
+mod tag_editor;
+mod visualizer;
+
+// Rows of the spectrum or oscilloscope under the play list
+const VISUALIZER_HEIGHT: u16 = 10;
 
-pub fn handle_theme(init_theme: InitTheme) -> Theme {
+pub fn handle_theme(init_theme: &InitTheme) -> Theme {
     Theme::new(init_theme)
 }
 
 pub fn draw(app: &mut App, theme: &Theme) -> Result<(), ExitFailure> {
-    let search_string = app.get_search_string();
-    let command_string = app.get_command_strign();
+    if app.cover_art.take_clear_screen() {
+        app.terminal.clear()?;
+    }
+    // The buffers hold what was typed, the prefix is only drawn
+    let search_string = if app.mode == Mode::Search || !app.search_buffer.is_empty() {
+        format!("|{}", app.get_search_string())
+    } else {
+        String::new()
+    };
+    let command = if app.mode == Mode::Command {
+        Some((
+            format!(":{}", app.command_line.text()),
+            app.command_line.cursor() + 1,
+        ))
+    } else {
+        None
+    };
+    let dupes_labels = match &app.dupes_report {
+        Some(report) => report.labels(&app.music_database),
+        None => Vec::new(),
+    };
+    let marked: Vec<bool> = (0..app.directory_contents.len())
+        .map(|index| app.is_marked(index))
+        .collect();
+    let progress: Vec<Option<u64>> = app
+        .directory_contents
+        .iter()
+        .map(|item| match item {
+            DirectoryItem::File(path) => app.stats.get(path).progress(),
+            DirectoryItem::Directory(_) => None,
+        })
+        .collect();
+    let help = app.keymap.help();
+    let commands_help = commands::help();
+    let mut graphics_area = None;
+    let mut mouse_areas = MouseAreas::default();
+    let envelope = app
+        .playing_music
+        .as_ref()
+        .and_then(|music| app.waveforms.envelope(&music.path));
     app.terminal.draw(|f| {
         let chunks = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Percentage(30), Constraint::Percentage(70)].as_ref())
             .split(f.size());
 
-        draw_music_list(
+        mouse_areas.music_list = Some(draw_music_list(
             f,
             chunks[0],
             theme,
-            app.window_height as usize,
-            &app.directory_contents,
-            &app.selection_index,
-            &search_string,
-            &command_string,
-            &app.error,
-        );
+            &MusicList {
+                window_height: app.window_height as usize,
+                files: &app.directory_contents,
+                labels: &dupes_labels,
+                marked: &marked,
+                progress: &progress,
+                selected_index: app.selection_index,
+                sort: sort::order_name(app.sorter.order),
+            },
+            &Prompt {
+                error: &app.error,
+                info: &app.info,
+                command: &command,
+                search_string: &search_string,
+            },
+        ));
 
         //Create the list chunks
         let chunks_right = Layout::default()
@@ -45,25 +114,120 @@ pub fn draw(app: &mut App, theme: &Theme) -> Result<(), ExitFailure> {
             .constraints([Constraint::Min(3), Constraint::Length(5)])
             .split(chunks[1]);
 
-        draw_play_music_list(
+        // The cover of the playing music, unless a form covers the play list
+        let mut play_list_area = chunks_right[0];
+        let modal = matches!(
+            app.mode,
+            Mode::Organize | Mode::TagEdit | Mode::Help | Mode::Delete
+        );
+        if let (Some(image), Some(protocol), Some(_), false) = (
+            app.cover_art.image(),
+            app.cover_art.protocol,
+            &app.playing_music,
+            modal,
+        ) {
+            let (cover_area, rest) = split_cover_area(chunks_right[0]);
+            graphics_area = draw_cover(f, cover_area, theme, image, protocol);
+            play_list_area = rest;
+        }
+
+        if app.visualizer.mode.is_some() {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(VISUALIZER_HEIGHT)])
+                .split(play_list_area);
+            play_list_area = chunks[0];
+            let active = app.playing_music.is_some() && !app.player.is_paused();
+            draw_visualizer(f, chunks[1], theme, &mut app.visualizer, active);
+        }
+
+        if let (true, Some(music)) = (app.lyrics_panel.visible, &app.playing_music) {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(play_list_area);
+            play_list_area = chunks[0];
+            draw_lyrics(f, chunks[1], theme, &app.lyrics_panel, music);
+        }
+
+        mouse_areas.play_list = Some(draw_play_music_list(
             f,
-            chunks_right[0],
+            play_list_area,
             &theme,
-            &app.play_music_list,
-            &app.playing_music,
-            app.player.is_paused()
-        );
+            &PlayList {
+                music_list: &app.play_music_list,
+                playing_music: &app.playing_music,
+                is_paused: app.player.is_paused(),
+                stats: &app.stats,
+                selection: match app.focus {
+                    Focus::PlayList => Some(app.play_list_selection),
+                    Focus::Browser => None,
+                },
+            },
+        ));
+        if app.play_music_list.is_empty() && app.playing_music.is_none() {
+            draw_home_page(f, &play_list_area, theme, &help);
+        }
 
-        draw_playing_music(
+        let (seek_bar, volume) = draw_playing_music(
             f,
             chunks_right[1],
             &theme,
             &app.playing_music,
             app.player.is_paused(),
             app.player.volume(),
-            &app.play_style
+            &app.play_style,
         );
+        draw_seek_bar(f, seek_bar, theme, &app.playing_music, envelope);
+        mouse_areas.seek_bar = Some(seek_bar);
+        mouse_areas.volume = Some(volume);
+
+        if app.mode == Mode::Organize {
+            if let Some(preview) = &app.organize_preview {
+                draw_organize_preview(
+                    f,
+                    chunks_right[0],
+                    theme,
+                    preview,
+                    &app.music_database,
+                    &app.keymap,
+                );
+            }
+        }
+
+        if app.mode == Mode::Delete {
+            if let Some(preview) = &app.delete_preview {
+                draw_delete_preview(
+                    f,
+                    chunks_right[0],
+                    theme,
+                    preview,
+                    &app.music_database,
+                    &app.keymap,
+                );
+            }
+        }
+
+        if app.mode == Mode::TagEdit {
+            if let Some(tag_editor) = &app.tag_editor {
+                draw_tag_editor(f, chunks_right[0], theme, tag_editor);
+            }
+        }
+
+        if app.mode == Mode::Help {
+            draw_help(
+                f,
+                f.size(),
+                theme,
+                &app.keymap,
+                &commands_help,
+                app.help_scroll,
+            );
+        }
     })?;
+    app.mouse_areas = mouse_areas;
+    app.cover_art
+        .draw_graphics(graphics_area, &mut io::stdout())?;
 
     Ok(())
 }
@@ -1,6 +1,6 @@
 use tui::backend::Backend;
 use tui::layout::{Alignment, Constraint, Direction, Layout, Rect};
-use tui::style::{Color, Style};
+use tui::style::{Color, Modifier, Style};
 use tui::text::{Span, Spans, Text};
 use tui::widgets::{Block, BorderType, Borders, Paragraph};
 use tui::Frame;
@@ -11,36 +11,89 @@ use crate::utils::split_path::split_path_to_name;
 use super::color::Theme;
 use super::display::Display;
 
+// The files or duplicates listed in the browser
+pub struct MusicList<'a> {
+    pub window_height: usize,
+    pub files: &'a [DirectoryItem],
+    // Shown instead of the file names when listing duplicates
+    pub labels: &'a [String],
+    // Whether each item is marked or in the visual selection
+    pub marked: &'a [bool],
+    // How much of each long file was heard, 100 once finished
+    pub progress: &'a [Option<u64>],
+    pub selected_index: Option<usize>,
+    // Name of the sort order of the files
+    pub sort: &'a str,
+}
+
+// The box above the list, the first of these that is set
+pub struct Prompt<'a> {
+    pub error: &'a Option<String>,
+    // Shown like an error, without its colors
+    pub info: &'a Option<String>,
+    // The command line and where its cursor is, in command mode
+    pub command: &'a Option<(String, usize)>,
+    pub search_string: &'a str,
+}
+
+// Returns the area of the rows and the index of the first one
 pub fn draw_music_list<B: Backend>(
     frame: &mut Frame<B>,
     area: Rect,
     theme: &Theme,
-    window_height: usize,
-    files: &Vec<DirectoryItem>,
-    selected_index: &Option<usize>,
-    search_string: &str,
-    command_string: &str,
-    error: &Option<String>,
-) {
-    let selected_index = match selected_index {
-        Some(index) => *index,
-        None => 0,
-    };
+    list: &MusicList,
+    prompt: &Prompt,
+) -> (Rect, usize) {
+    let MusicList {
+        window_height,
+        files,
+        labels,
+        marked,
+        progress,
+        selected_index,
+        sort,
+    } = *list;
+    let Prompt {
+        error,
+        info,
+        command,
+        search_string,
+    } = *prompt;
+    let selected_index = selected_index.unwrap_or(0);
     // let selected_index = selected_index.unwrap();
     let display = Display::new(window_height, files.len(), selected_index);
     let mut music_names: Vec<Spans> = Vec::new();
 
     // List block
+    let title = if labels.is_empty() {
+        " Music list "
+    } else {
+        " Duplicates "
+    };
+    let mut title_spans = vec![
+        Span::styled(title, Style::default().fg(theme.list_title_color)),
+        Span::styled(
+            format!("Page: {}/{} ", display.page.0, display.page.1),
+            Style::default().fg(theme.list_title_page_color),
+        ),
+    ];
+    if labels.is_empty() {
+        title_spans.push(Span::styled(
+            format!("Sort: {} ", sort),
+            Style::default().fg(theme.list_title_page_color),
+        ));
+    }
+    let marked_count = marked.iter().filter(|marked| **marked).count();
+    if marked_count > 0 {
+        title_spans.push(Span::styled(
+            format!("{} marked ", marked_count),
+            Style::default().fg(theme.list_marked_color),
+        ));
+    }
     let block = Block::default()
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
-        .title(Spans::from(vec![
-            Span::styled(" Music list ", Style::default().fg(theme.list_title_color)),
-            Span::styled(
-                format!("Page: {}/{} ", display.page.0, display.page.1),
-                Style::default().fg(theme.list_title_page_color),
-            ),
-        ]))
+        .title(Spans::from(title_spans))
         .title_alignment(Alignment::Center)
         .style(Style::default().fg(theme.list_border_color));
     frame.render_widget(block, area);
@@ -49,16 +102,35 @@ pub fn draw_music_list<B: Backend>(
     if files.len() > 0 {
         //Convert DirectoryItems to Text
         for i in display.from..display.to {
+            let is_marked = marked.get(i).copied().unwrap_or(false);
             match &files[i] {
                 DirectoryItem::File(path) => {
-                    let name = split_path_to_name(path);
+                    let name = match labels.get(i) {
+                        Some(label) => label.as_str(),
+                        None => split_path_to_name(path),
+                    };
 
-                    music_names.push(get_spans(
+                    let mut spans = get_spans(
                         "  ".to_string(),
                         name.to_string(),
                         theme.list_icon_color,
-                        theme.list_music_color,
-                    ));
+                        if is_marked {
+                            theme.list_marked_color
+                        } else {
+                            theme.list_music_color
+                        },
+                    );
+                    if let Some(progress) = progress.get(i).copied().flatten() {
+                        let label = match progress {
+                            100 => String::from(" ✓"),
+                            progress => format!(" {}%", progress),
+                        };
+                        spans.0.push(Span::styled(
+                            label,
+                            Style::default().fg(theme.play_music_list_stats_color),
+                        ));
+                    }
+                    music_names.push(spans);
                 }
                 DirectoryItem::Directory(path) => {
                     let name = split_path_to_name(path);
@@ -67,7 +139,11 @@ pub fn draw_music_list<B: Backend>(
                         "  ".to_string(),
                         name.to_string(),
                         theme.list_icon_color,
-                        theme.list_folder_color,
+                        if is_marked {
+                            theme.list_marked_color
+                        } else {
+                            theme.list_folder_color
+                        },
                     ));
                 }
             }
@@ -82,15 +158,14 @@ pub fn draw_music_list<B: Backend>(
             .map(|span| span.content.to_string())
             .collect();
         icon_name[1].insert_str(0, "");
-        music_names.insert(
-            remove_num,
-            get_spans(
-                icon_name[0].to_string(),
-                icon_name[1].to_string(),
-                theme.list_icon_color,
-                theme.list_selected_color,
-            ),
+        let mut selected = get_spans(
+            icon_name[0].to_string(),
+            icon_name[1].to_string(),
+            theme.list_icon_color,
+            theme.list_selected_color,
         );
+        selected.0.extend(names.0.into_iter().skip(2));
+        music_names.insert(remove_num, selected);
     }
 
     //Create the list chunks
@@ -103,15 +178,18 @@ pub fn draw_music_list<B: Backend>(
     if let Some(error) = error {
         // Display error block
         draw_error(frame, chunks[0], error);
-    } else if command_string.len() != 0 {
-        // Display search block
-        draw_command(frame, chunks[0], theme, command_string);
+    } else if let Some(info) = info {
+        draw_info(frame, chunks[0], theme, info);
+    } else if let Some((command_string, cursor)) = command {
+        draw_command(frame, chunks[0], theme, command_string, *cursor);
     } else {
         draw_search(frame, chunks[0], theme, search_string);
     }
 
     // Display musics and folders
     frame.render_widget(Paragraph::new(music_names), chunks[1]);
+
+    (chunks[1], display.from)
 }
 
 fn get_spans(icon: String, name: String, icon_color: Color, name_color: Color) -> Spans<'static> {
@@ -135,11 +213,30 @@ fn draw_search<B: Backend>(frame: &mut Frame<B>, area: Rect, theme: &Theme, sear
     frame.render_widget(search, area);
 }
 
-fn draw_command<B: Backend>(frame: &mut Frame<B>, area: Rect, theme: &Theme, command_string: &str) {
-    let text = Text::from(Spans::from(vec![Span::styled(
-        command_string,
-        Style::default().fg(theme.command_font_color),
-    )]));
+fn draw_command<B: Backend>(
+    frame: &mut Frame<B>,
+    area: Rect,
+    theme: &Theme,
+    command_string: &str,
+    cursor: usize,
+) {
+    // Long lines scroll to keep the cursor in view
+    let width = area.width.saturating_sub(2) as usize;
+    let chars: Vec<char> = command_string.chars().collect();
+    let start = (cursor + 1).saturating_sub(width);
+    let style = Style::default().fg(theme.command_font_color);
+    let before: String = chars[start.min(chars.len())..cursor.min(chars.len())]
+        .iter()
+        .collect();
+    let under_cursor = chars
+        .get(cursor)
+        .map_or(String::from(" "), |chr| chr.to_string());
+    let after: String = chars.iter().skip(cursor + 1).collect();
+    let text = Text::from(Spans::from(vec![
+        Span::styled(before, style),
+        Span::styled(under_cursor, style.add_modifier(Modifier::REVERSED)),
+        Span::styled(after, style),
+    ]));
     let command_paragraph = Paragraph::new(text).block(
         Block::default()
             .borders(Borders::ALL)
@@ -150,11 +247,29 @@ fn draw_command<B: Backend>(frame: &mut Frame<B>, area: Rect, theme: &Theme, com
     frame.render_widget(command_paragraph, area);
 }
 
+fn draw_info<B: Backend>(frame: &mut Frame<B>, area: Rect, theme: &Theme, info: &str) {
+    let text = Spans::from(Span::styled(
+        info,
+        Style::default().fg(theme.command_font_color),
+    ));
+    let info_paragraph = Paragraph::new(text).block(
+        Block::default()
+            .title(" Info ")
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .style(Style::default().fg(theme.command_border_color)),
+    );
+    frame.render_widget(info_paragraph, area);
+}
+
 fn draw_error<B: Backend>(frame: &mut Frame<B>, area: Rect, error: &str) {
     let text = Spans::from(Span::styled(error, Style::default().fg(Color::LightRed)));
     let err_paragraph = Paragraph::new(text).block(
         Block::default()
-            .title(Span::styled(" Error ", Style::default().fg(Color::LightRed)))
+            .title(Span::styled(
+                " Error ",
+                Style::default().fg(Color::LightRed),
+            ))
             .borders(Borders::ALL)
             .border_type(BorderType::Rounded)
             .style(Style::default().fg(Color::LightRed)),
@@ -0,0 +1,86 @@
+use tui::backend::Backend;
+use tui::layout::{Alignment, Rect};
+use tui::style::{Color, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, BorderType, Borders, Clear, Paragraph};
+use tui::Frame;
+
+use crate::keymap::{Action, Keymap};
+use crate::organize::Preview;
+
+use super::color::Theme;
+
+// Dry run of `:organize`, every move shown as a removed and an added path
+pub fn draw_organize_preview<B: Backend>(
+    frame: &mut Frame<B>,
+    area: Rect,
+    theme: &Theme,
+    preview: &Preview,
+    music_database: &str,
+    keymap: &Keymap,
+) {
+    let relative = |path: &str| {
+        path.strip_prefix(music_database)
+            .unwrap_or(path)
+            .trim_start_matches('/')
+            .to_string()
+    };
+    let conflicts = preview.moves.iter().filter(|m| m.conflict).count();
+
+    let mut lines: Vec<Spans> = Vec::new();
+    for m in preview.moves.iter().skip(preview.scroll) {
+        lines.push(Spans::from(Span::styled(
+            format!(" - {}", relative(&m.from)),
+            Style::default().fg(Color::LightRed),
+        )));
+        if m.conflict {
+            lines.push(Spans::from(Span::styled(
+                format!(" ! {} (taken, skipped)", relative(&m.to)),
+                Style::default().fg(Color::Yellow),
+            )));
+        } else {
+            lines.push(Spans::from(Span::styled(
+                format!(" + {}", relative(&m.to)),
+                Style::default().fg(Color::LightGreen),
+            )));
+        }
+    }
+
+    let mut title = vec![
+        Span::styled(
+            format!(" Organize: {} ", preview.pattern),
+            Style::default().fg(theme.play_music_list_title_color),
+        ),
+        Span::styled(" | ", Style::default().fg(Color::Yellow)),
+        Span::styled(
+            format!(" {} moves ", preview.moves.len() - conflicts),
+            Style::default().fg(theme.play_music_list_title_color),
+        ),
+    ];
+    if conflicts > 0 {
+        title.push(Span::styled(" | ", Style::default().fg(Color::Yellow)));
+        title.push(Span::styled(
+            format!(" {} skipped ", conflicts),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    title.push(Span::styled(" | ", Style::default().fg(Color::Yellow)));
+    title.push(Span::styled(
+        format!(
+            " [{}] apply [{}] cancel ",
+            keymap.key_of(Action::Confirm),
+            keymap.key_of(Action::Cancel)
+        ),
+        Style::default().fg(theme.usage_color_right),
+    ));
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(title)
+        .title_alignment(Alignment::Center)
+        .style(Style::default().fg(theme.play_music_list_border_color));
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
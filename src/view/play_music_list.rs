@@ -6,6 +6,8 @@ use tui::widgets::{Block, BorderType, Borders, Paragraph};
 use tui::Frame;
 
 use crate::music::Music;
+use crate::stats::{Stats, TrackStats};
+use crate::utils::timestamp::now_timestamp;
 
 use super::color::Theme;
 
@@ -19,34 +21,30 @@ const MUSIC_PLAYER_PIC: &[&str] = &[
     "╚═╝     ╚═╝"," ╚═════╝ ","╚══════╝","╚═╝"," ╚═════╝",
 ];
 const CUT_OFF_RULE: &str = "   ";
-#[rustfmt::skip]
-const USAGE: &[&str] = &[
-    "Move selection up              ", "[k, K] ", " ",
-    "Move selection down            ", "[j, J] ", " ",
-    "Move selection to the top      ", "[g]    ", " ",
-    "Move selection to the bottom   ", "[G]    ", " ",
-    "Next page                      ", "[n]    ", " ",
-    "Previous page                  ", "[N]    ", " ",
-    "Open folder                    ", "[l]    ", " ",
-    "Back to previous folder        ", "[h]    ", " ",
-    "Enter search mode              ", "[|]    ", " ",
-    "Enter command mode             ", "[:]    ", " ",
-    "Exit program                   ", "[q]    ", " ",
-    "Exit search or command mode    ", "[Esc]  ", " ",
-    "Pause or resume the music      ", "[Space]", " ",
-    "Decrease volume                ", "[-]    ", " ",
-    "Increase volume                ", "[+, =] ", " ",
-    "Add music to the playlist      ", "[Enter]",
-];
 
+// What the play list shows
+pub struct PlayList<'a> {
+    pub music_list: &'a [Music],
+    pub playing_music: &'a Option<Music>,
+    pub is_paused: bool,
+    pub stats: &'a Stats,
+    // Row of the cursor while the play list has the focus
+    pub selection: Option<usize>,
+}
+
+// Returns the area of the rows and the index of the first one
 pub fn draw_play_music_list<B: Backend>(
     frame: &mut Frame<B>,
     area: Rect,
     theme: &Theme,
-    music_list: &Vec<Music>,
-    playing_music: &Option<Music>,
-    is_paused: bool,
-) {
+    play_list: &PlayList,
+) -> (Rect, usize) {
+    let PlayList {
+        music_list,
+        playing_music,
+        selection,
+        ..
+    } = *play_list;
     let mut all_music_dur: u64 = 0;
     for music in music_list {
         all_music_dur += music.total_duration.as_secs();
@@ -88,23 +86,41 @@ pub fn draw_play_music_list<B: Backend>(
         ));
     };
 
-    // Play music list block
+    // Play music list block, highlighted while it has the focus
+    let border_color = match selection {
+        Some(_) => theme.list_selected_color,
+        None => theme.play_music_list_border_color,
+    };
     let block = Block::default()
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
         .title(title_spans)
         .title_alignment(Alignment::Center)
-        .style(Style::default().fg(theme.play_music_list_border_color));
+        .style(Style::default().fg(border_color));
     frame.render_widget(block, area);
 
+    // Below the borders and the header
+    let rows = Rect::new(
+        area.x + 1,
+        area.y + 2,
+        area.width.saturating_sub(2),
+        area.height.saturating_sub(3),
+    );
     if music_list.len() > 0 || playing_music != &None {
-        draw_play_list(frame, &area, theme, music_list, &playing_music, is_paused);
-    } else {
-        draw_home_page(frame, &area, theme);
+        let offset = draw_play_list(frame, &area, theme, play_list);
+        return (rows, offset);
     }
+
+    (rows, 0)
 }
 
-fn draw_home_page<B: Backend>(frame: &mut Frame<B>, area: &Rect, theme: &Theme) {
+// The logo and the keys of the browse mode, in the empty play list
+pub fn draw_home_page<B: Backend>(
+    frame: &mut Frame<B>,
+    area: &Rect,
+    theme: &Theme,
+    help: &[(&str, String)],
+) {
     let inner_rect = Rect::new(area.x + 1, area.y + 2, area.width - 2, area.height - 3);
     let chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -132,11 +148,18 @@ fn draw_home_page<B: Backend>(frame: &mut Frame<B>, area: &Rect, theme: &Theme)
         CUT_OFF_RULE,
         Style::default().fg(theme.cut_off_rule_color),
     ))];
-    let usage_split = USAGE.split(|s| *s == " ");
-    for line in usage_split {
+    let description_width = help.iter().map(|(description, _)| description.len()).max();
+    let keys_width = help.iter().map(|(_, keys)| keys.chars().count()).max();
+    for (description, keys) in help {
         usage.push(Spans::from(vec![
-            Span::styled(line[0], Style::default().fg(theme.usage_color_left)),
-            Span::styled(line[1], Style::default().fg(theme.usage_color_right)),
+            Span::styled(
+                format!("{:<1$} ", description, description_width.unwrap_or(0)),
+                Style::default().fg(theme.usage_color_left),
+            ),
+            Span::styled(
+                format!("{:<1$}", keys, keys_width.unwrap_or(0)),
+                Style::default().fg(theme.usage_color_right),
+            ),
         ]))
     }
 
@@ -150,13 +173,20 @@ fn draw_play_list<B: Backend>(
     frame: &mut Frame<B>,
     area: &Rect,
     theme: &Theme,
-    music_list: &Vec<Music>,
-    playing_music: &Option<Music>,
-    is_paused: bool,
-) {
+    play_list: &PlayList,
+) -> usize {
+    let PlayList {
+        music_list,
+        playing_music,
+        is_paused,
+        stats,
+        selection,
+    } = *play_list;
     let mut names: Vec<Spans> = Vec::new();
     let mut artists: Vec<Spans> = Vec::new();
     let mut albums: Vec<Spans> = Vec::new();
+    let mut track_stats: Vec<Spans> = Vec::new();
+    let now = now_timestamp();
 
     if let Some(playing_music) = playing_music {
         let playing_music_s = playing_music.total_duration.as_secs() % 60;
@@ -190,6 +220,8 @@ fn draw_play_list<B: Backend>(
             format!(" {}", &playing_music.album),
             Style::default().fg(theme.play_music_list_album_color),
         )]));
+
+        track_stats.push(get_stats_spans(theme, stats.get(&playing_music.path), now));
     }
 
     for (i, music) in music_list.iter().enumerate() {
@@ -219,6 +251,29 @@ fn draw_play_list<B: Backend>(
             format!(" {}", &music.album),
             Style::default().fg(theme.play_music_list_album_color),
         )]));
+
+        track_stats.push(get_stats_spans(theme, stats.get(&music.path), now));
+    }
+
+    // The cursor row stands out, and the rows scroll to keep it in view
+    let rows = area.height.saturating_sub(3) as usize;
+    let mut offset = 0;
+    if let Some(selection) = selection.map(|s| s.min(names.len().saturating_sub(1))) {
+        if let Some(spans) = names.get_mut(selection) {
+            for span in spans.0.iter_mut().skip(2) {
+                span.style = Style::default()
+                    .fg(theme.list_selected_color)
+                    .add_modifier(Modifier::BOLD);
+            }
+            spans.0.insert(
+                2,
+                Span::styled("", Style::default().fg(theme.list_icon_color)),
+            );
+        }
+        offset = (selection + 1).saturating_sub(rows);
+    }
+    for column in [&mut names, &mut artists, &mut albums, &mut track_stats] {
+        column.drain(..offset.min(column.len()));
     }
 
     names.insert(
@@ -242,19 +297,57 @@ fn draw_play_list<B: Backend>(
             Style::default().fg(theme.play_music_list_header_color),
         )]),
     );
+    track_stats.insert(
+        0,
+        Spans::from(vec![Span::styled(
+            format!(" {:<5} {:>5} {:>4}", "Rate", "Plays", "Last"),
+            Style::default().fg(theme.play_music_list_header_color),
+        )]),
+    );
 
     //Create the list chunks
     let inner_rect = Rect::new(area.x + 1, area.y + 1, area.width - 2, area.height - 2);
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
-            Constraint::Percentage(45),
-            Constraint::Percentage(20),
-            Constraint::Percentage(35),
+            Constraint::Percentage(40),
+            Constraint::Percentage(18),
+            Constraint::Percentage(24),
+            Constraint::Percentage(18),
         ])
         .split(inner_rect);
 
     frame.render_widget(Paragraph::new(names), chunks[0]);
     frame.render_widget(Paragraph::new(artists), chunks[1]);
     frame.render_widget(Paragraph::new(albums), chunks[2]);
+    frame.render_widget(Paragraph::new(track_stats), chunks[3]);
+
+    offset
+}
+
+// Rating stars, play count and how long ago the music was last played
+fn get_stats_spans(theme: &Theme, track_stats: &TrackStats, now: u64) -> Spans<'static> {
+    let rating = track_stats.rating.min(5) as usize;
+    let last_played = match track_stats.last_played {
+        Some(last_played) => {
+            let ago = now.saturating_sub(last_played);
+            match ago {
+                a if a < 60 * 60 => format!("{}m", a / 60),
+                a if a < 24 * 60 * 60 => format!("{}h", a / 60 / 60),
+                a => format!("{}d", a / 60 / 60 / 24),
+            }
+        }
+        None => String::from("-"),
+    };
+
+    Spans::from(vec![Span::styled(
+        format!(
+            " {}{} {:>5} {:>4}",
+            "★".repeat(rating),
+            "☆".repeat(5 - rating),
+            track_stats.play_count,
+            last_played,
+        ),
+        Style::default().fg(theme.play_music_list_stats_color),
+    )])
 }
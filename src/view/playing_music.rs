@@ -1,8 +1,9 @@
 use tui::backend::Backend;
+use tui::buffer::Buffer;
 use tui::layout::{Alignment, Rect};
 use tui::style::{Modifier, Style};
 use tui::text::Span;
-use tui::widgets::{Block, BorderType, Borders, Gauge};
+use tui::widgets::{Block, BorderType, Borders, Widget};
 use tui::Frame;
 
 use crate::app::PlayStyle;
@@ -10,6 +11,7 @@ use crate::music::Music;
 
 use super::color::Theme;
 
+// Returns the area left for the seek bar and the area of the volume
 pub fn draw_playing_music<B: Backend>(
     frame: &mut Frame<B>,
     area: Rect,
@@ -18,10 +20,8 @@ pub fn draw_playing_music<B: Backend>(
     is_paused: bool,
     volume: f32,
     play_style: &PlayStyle,
-) {
+) -> (Rect, Rect) {
     let mut play_style_icon = "劣";
-    let mut label = "";
-    let mut percent = 0;
     match play_style {
         PlayStyle::SingleCycle => play_style_icon = "綾",
         _ => {}
@@ -35,18 +35,7 @@ pub fn draw_playing_music<B: Backend>(
     let mut gauge_title: Vec<Span> = Vec::new();
 
     if let Some(music) = playing_music {
-        if is_paused {
-            label = "  ";
-        } else {
-            label = "  ";
-        }
-
-        percent = ((music.play_position.as_secs_f32() / music.total_duration.as_secs_f32())
-            * (100 as f32))
-            .round() as u16;
-        if percent > 100 {
-            percent = 100;
-        }
+        let label = if is_paused { "  " } else { "  " };
 
         block_title.push(Span::styled(
             "ﱘ ",
@@ -61,9 +50,13 @@ pub fn draw_playing_music<B: Backend>(
 
         let play_dur = music.play_position.as_secs();
         let total_dur = music.total_duration.as_secs();
+        gauge_title.push(Span::styled(
+            label,
+            Style::default().fg(theme.gauge_label_color),
+        ));
         gauge_title.push(Span::styled(
             format!(
-                " [ {}m {}s : {}m {}s ] {} ",
+                "[ {}m {}s : {}m {}s ] {} ",
                 play_dur / 60,
                 play_dur % 60,
                 total_dur / 60,
@@ -75,6 +68,7 @@ pub fn draw_playing_music<B: Backend>(
     }
 
     // Volume
+    let volume_start: usize = block_title.iter().map(|span| span.width()).sum();
     {
         block_title.push(Span::styled(
             match volume {
@@ -92,6 +86,17 @@ pub fn draw_playing_music<B: Backend>(
         ));
     }
 
+    // Where the centered title puts the volume
+    let title_width: usize = block_title.iter().map(|span| span.width()).sum();
+    let title_x = area.x + area.width.saturating_sub(title_width as u16) / 2;
+    let volume_rect = Rect::new(
+        title_x + volume_start as u16,
+        area.y,
+        (title_width - volume_start) as u16,
+        1,
+    )
+    .intersection(area);
+
     // Playing music block
     let block = Block::default()
         .borders(Borders::ALL)
@@ -102,19 +107,81 @@ pub fn draw_playing_music<B: Backend>(
     frame.render_widget(block, area);
 
     let inner_rect = Rect::new(area.x + 1, area.y + 1, area.width - 2, area.height - 2);
-    let gauge = Gauge::default()
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .border_type(BorderType::Thick)
-                .border_style(Style::default().fg(theme.gauge_border_color))
-                .title(gauge_title),
-        )
-        .gauge_style(Style::default().fg(theme.gauge_color))
-        .label(Span::styled(
-            label,
-            Style::default().fg(theme.gauge_label_color),
-        ))
-        .percent(percent);
-    frame.render_widget(gauge, inner_rect);
+    let gauge_block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Thick)
+        .border_style(Style::default().fg(theme.gauge_border_color))
+        .title(gauge_title);
+    let seek_bar_rect = gauge_block.inner(inner_rect);
+    frame.render_widget(gauge_block, inner_rect);
+
+    (seek_bar_rect, volume_rect)
+}
+
+pub fn draw_seek_bar<B: Backend>(
+    frame: &mut Frame<B>,
+    area: Rect,
+    theme: &Theme,
+    playing_music: &Option<Music>,
+    envelope: Option<&[f32]>,
+) {
+    let ratio = match playing_music {
+        Some(music) => {
+            (music.play_position.as_secs_f64() / music.total_duration.as_secs_f64()).clamp(0.0, 1.0)
+        }
+        None => 0.0,
+    };
+    frame.render_widget(
+        SeekBar {
+            envelope: envelope.unwrap_or(&[]),
+            ratio,
+            played_style: Style::default().fg(theme.gauge_color),
+            unplayed_style: Style::default().fg(theme.waveform_unplayed_color),
+        },
+        area,
+    );
+}
+
+const LEVELS: [&str; 8] = ["▁", "▂", "▃", "▄", "▅", "▆", "▇", "█"];
+
+// The loudness of the song along its length, a flat line until it has been computed
+struct SeekBar<'a> {
+    envelope: &'a [f32],
+    ratio: f64,
+    played_style: Style,
+    unplayed_style: Style,
+}
+
+impl<'a> Widget for SeekBar<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let width = area.width as usize;
+        for column in 0..width {
+            // The loudest window under the column
+            let first = column * self.envelope.len() / width;
+            let last = ((column + 1) * self.envelope.len() / width).max(first + 1);
+            let level = self
+                .envelope
+                .get(first..last.min(self.envelope.len()))
+                .unwrap_or(&[])
+                .iter()
+                .copied()
+                .fold(0.0, f32::max);
+            // In eighths of a row, silence still shows as a line
+            let height = area.height as usize;
+            let eighths = ((level * (height * 8) as f32).round() as usize).clamp(1, height * 8);
+
+            let style = if (column as f64 + 0.5) / width as f64 <= self.ratio {
+                self.played_style
+            } else {
+                self.unplayed_style
+            };
+            for row in 0..height {
+                let filled = eighths.saturating_sub((height - 1 - row) * 8).min(8);
+                let symbol = if filled == 0 { " " } else { LEVELS[filled - 1] };
+                buf.get_mut(area.x + column as u16, area.y + row as u16)
+                    .set_symbol(symbol)
+                    .set_style(style);
+            }
+        }
+    }
 }
@@ -0,0 +1,69 @@
+use tui::backend::Backend;
+use tui::layout::{Alignment, Rect};
+use tui::style::{Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, BorderType, Borders, Clear, Paragraph};
+use tui::Frame;
+
+use crate::tag_editor::{TagEditor, TAG_FIELDS};
+
+use super::color::Theme;
+
+pub fn draw_tag_editor<B: Backend>(
+    frame: &mut Frame<B>,
+    area: Rect,
+    theme: &Theme,
+    tag_editor: &TagEditor,
+) {
+    // Center the form over the given area
+    let width = (area.width * 3 / 4).max(30).min(area.width);
+    let height = (TAG_FIELDS.len() as u16 + 4).min(area.height);
+    let rect = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+
+    let mut lines: Vec<Spans> = vec![Spans::default()];
+    for (i, field) in TAG_FIELDS.iter().enumerate() {
+        let mut value_style = Style::default().fg(theme.usage_color_right);
+        let mut value = tag_editor.values[i].clone();
+        if tag_editor.mixed[i] && !tag_editor.edited[i] {
+            value = String::from("<multiple values>");
+            value_style = value_style.add_modifier(Modifier::DIM);
+        }
+        if i == tag_editor.selected {
+            value_style = Style::default()
+                .fg(theme.list_selected_color)
+                .add_modifier(Modifier::BOLD);
+            value.push('▏');
+        }
+
+        lines.push(Spans::from(vec![
+            Span::styled(
+                format!(" {:>7} : ", field),
+                Style::default().fg(theme.usage_color_left),
+            ),
+            Span::styled(value, value_style),
+        ]));
+    }
+
+    let title = if tag_editor.paths.len() == 1 {
+        String::from(" Edit tags ")
+    } else {
+        format!(" Edit tags of {} files ", tag_editor.paths.len())
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(Span::styled(
+            title,
+            Style::default().fg(theme.play_music_list_title_color),
+        ))
+        .title_alignment(Alignment::Center)
+        .style(Style::default().fg(theme.command_border_color));
+
+    frame.render_widget(Clear, rect);
+    frame.render_widget(Paragraph::new(lines).block(block), rect);
+}
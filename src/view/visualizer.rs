@@ -0,0 +1,111 @@
+use tui::backend::Backend;
+use tui::layout::{Alignment, Rect};
+use tui::style::Style;
+use tui::symbols::Marker;
+use tui::text::Span;
+use tui::widgets::canvas::{Canvas, Line};
+use tui::widgets::{BarChart, Block, BorderType, Borders};
+use tui::Frame;
+
+use crate::visualizer::{Visualizer, VisualizerMode};
+
+use super::color::Theme;
+
+const BAR_GAP: u16 = 1;
+// Samples drawn by the oscilloscope, about 23ms at 44.1kHz
+const WAVEFORM_SAMPLES: usize = 1024;
+
+// `active` is false when nothing is playing or the music is paused
+pub fn draw_visualizer<B: Backend>(
+    frame: &mut Frame<B>,
+    area: Rect,
+    theme: &Theme,
+    visualizer: &mut Visualizer,
+    active: bool,
+) {
+    let mode = match visualizer.mode {
+        Some(mode) => mode,
+        None => return,
+    };
+    let title = match mode {
+        VisualizerMode::Spectrum => " Spectrum ",
+        VisualizerMode::Oscilloscope => " Oscilloscope ",
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(Span::styled(
+            title,
+            Style::default().fg(theme.play_music_list_title_color),
+        ))
+        .title_alignment(Alignment::Center)
+        .style(Style::default().fg(theme.play_music_list_border_color));
+    let inner = block.inner(area);
+    if inner.width == 0 || inner.height < 2 {
+        frame.render_widget(block, area);
+        return;
+    }
+
+    match mode {
+        VisualizerMode::Spectrum => {
+            // Bars two cells wide when there is room, the last row is left for labels
+            let bar_width = if inner.width >= 48 { 2 } else { 1 };
+            let bands = ((inner.width + BAR_GAP) / (bar_width + BAR_GAP)) as usize;
+            let scale = (inner.height as u64 - 1) * 8;
+            let data: Vec<(&str, u64)> = visualizer
+                .spectrum(bands, active)
+                .iter()
+                .map(|level| ("", (level * scale as f32).round() as u64))
+                .collect();
+            let style = Style::default().fg(theme.spectrum_color);
+            let bar_chart = BarChart::default()
+                .block(block)
+                .data(&data)
+                .max(scale)
+                .bar_width(bar_width)
+                .bar_gap(BAR_GAP)
+                .bar_style(style)
+                // Values would be printed over the bars
+                .value_style(style.bg(theme.spectrum_color));
+            frame.render_widget(bar_chart, area);
+        }
+        VisualizerMode::Oscilloscope => {
+            let samples = if active {
+                visualizer.waveform(WAVEFORM_SAMPLES)
+            } else {
+                Vec::new()
+            };
+            // Braille dots are two per cell across
+            let points = (inner.width as usize * 2).min(samples.len());
+            let color = theme.oscilloscope_color;
+            let canvas = Canvas::default()
+                .block(block)
+                .marker(Marker::Braille)
+                .x_bounds([0.0, points.max(2) as f64 - 1.0])
+                .y_bounds([-1.0, 1.0])
+                .paint(|ctx| {
+                    if points < 2 {
+                        ctx.draw(&Line {
+                            x1: 0.0,
+                            y1: 0.0,
+                            x2: 1.0,
+                            y2: 0.0,
+                            color,
+                        });
+                        return;
+                    }
+                    let sample = |i: usize| samples[i * samples.len() / points] as f64;
+                    for i in 1..points {
+                        ctx.draw(&Line {
+                            x1: (i - 1) as f64,
+                            y1: sample(i - 1),
+                            x2: i as f64,
+                            y2: sample(i),
+                            color,
+                        });
+                    }
+                });
+            frame.render_widget(canvas, area);
+        }
+    }
+}
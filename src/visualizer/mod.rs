@@ -0,0 +1,200 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rodio::Source;
+
+use self::fft::fft;
+
+mod fft;
+
+// Samples kept for the FFT, about 46ms at 44.1kHz
+const FFT_SIZE: usize = 2048;
+// Samples are handed over to the visualizer in chunks to keep locking rare
+const CHUNK_SIZE: usize = 256;
+// Bars are spread logarithmically over this range
+const MIN_FREQUENCY: f32 = 40.0;
+const MAX_FREQUENCY: f32 = 16000.0;
+// Levels go from -70dB to 0dB
+const DYNAMIC_RANGE: f32 = 70.0;
+// How much a bar may drop per frame
+const FALL: f32 = 0.05;
+
+struct Samples {
+    data: VecDeque<f32>,
+    sample_rate: u32,
+}
+
+// Passes the samples of `source` through to the sink and keeps a mono copy of them
+pub struct Tap<S> {
+    source: S,
+    samples: Arc<Mutex<Samples>>,
+    pending: Vec<f32>,
+    channel: u16,
+    frame_sum: f32,
+}
+
+impl<S> Iterator for Tap<S>
+where
+    S: Source<Item = i16>,
+{
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        let sample = self.source.next()?;
+        self.frame_sum += sample as f32 / i16::MAX as f32;
+        self.channel += 1;
+
+        let channels = self.source.channels().max(1);
+        if self.channel >= channels {
+            self.pending.push(self.frame_sum / channels as f32);
+            self.channel = 0;
+            self.frame_sum = 0.0;
+            if self.pending.len() >= CHUNK_SIZE {
+                if let Ok(mut samples) = self.samples.lock() {
+                    samples.sample_rate = self.source.sample_rate();
+                    samples.data.extend(self.pending.drain(..));
+                    let excess = samples.data.len().saturating_sub(FFT_SIZE);
+                    samples.data.drain(..excess);
+                }
+            }
+        }
+
+        Some(sample)
+    }
+}
+
+impl<S> Source for Tap<S>
+where
+    S: Source<Item = i16>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum VisualizerMode {
+    Spectrum,
+    Oscilloscope,
+}
+
+pub struct Visualizer {
+    pub mode: Option<VisualizerMode>,
+    samples: Arc<Mutex<Samples>>,
+    // Bar heights of the last frame, from 0 to 1
+    levels: Vec<f32>,
+}
+
+impl Visualizer {
+    pub fn new() -> Visualizer {
+        Visualizer {
+            mode: None,
+            samples: Arc::new(Mutex::new(Samples {
+                data: VecDeque::with_capacity(FFT_SIZE + CHUNK_SIZE),
+                sample_rate: 44100,
+            })),
+            levels: Vec::new(),
+        }
+    }
+
+    // Spectrum, oscilloscope, then hidden
+    pub fn toggle(&mut self) {
+        self.mode = match self.mode {
+            None => Some(VisualizerMode::Spectrum),
+            Some(VisualizerMode::Spectrum) => Some(VisualizerMode::Oscilloscope),
+            Some(VisualizerMode::Oscilloscope) => None,
+        };
+        self.levels.clear();
+    }
+
+    // Wraps a source on its way to the sink, the samples left by the previous one are dropped
+    pub fn tap<S: Source<Item = i16>>(&self, source: S) -> Tap<S> {
+        if let Ok(mut samples) = self.samples.lock() {
+            samples.data.clear();
+        }
+
+        Tap {
+            source,
+            samples: Arc::clone(&self.samples),
+            pending: Vec::with_capacity(CHUNK_SIZE),
+            channel: 0,
+            frame_sum: 0.0,
+        }
+    }
+
+    fn last_samples(&self) -> (Vec<f32>, u32) {
+        match self.samples.lock() {
+            Ok(samples) => (samples.data.iter().copied().collect(), samples.sample_rate),
+            Err(_) => (Vec::new(), 44100),
+        }
+    }
+
+    // `bands` bar heights from 0 to 1, bars fall back slowly instead of jumping down,
+    // and to nothing while the music is paused
+    pub fn spectrum(&mut self, bands: usize, active: bool) -> &[f32] {
+        let mut targets = vec![0.0; bands];
+        let (samples, sample_rate) = self.last_samples();
+        if active && !samples.is_empty() {
+            let mut re = vec![0.0; FFT_SIZE];
+            let mut im = vec![0.0; FFT_SIZE];
+            let start = FFT_SIZE - samples.len().min(FFT_SIZE);
+            for (i, sample) in samples.iter().rev().take(FFT_SIZE).rev().enumerate() {
+                // Hann window
+                let window = 0.5 - 0.5 * (2.0 * PI * (start + i) as f32 / FFT_SIZE as f32).cos();
+                re[start + i] = sample * window;
+            }
+            fft(&mut re, &mut im);
+
+            // Amplitude of a full scale sine is 1, the window halves it
+            let magnitude =
+                |bin: usize| (re[bin].powi(2) + im[bin].powi(2)).sqrt() * 4.0 / FFT_SIZE as f32;
+            let bin_width = sample_rate as f32 / FFT_SIZE as f32;
+            let max_frequency = MAX_FREQUENCY.min(sample_rate as f32 / 2.0);
+            let ratio = (max_frequency / MIN_FREQUENCY).powf(1.0 / bands as f32);
+            for (band, target) in targets.iter_mut().enumerate() {
+                let low = MIN_FREQUENCY * ratio.powi(band as i32);
+                let first = ((low / bin_width).round() as usize).clamp(1, FFT_SIZE / 2 - 1);
+                let last =
+                    (((low * ratio) / bin_width).round() as usize).clamp(first + 1, FFT_SIZE / 2);
+                let peak = (first..last).map(magnitude).fold(0.0, f32::max);
+                let db = 20.0 * peak.max(1e-9).log10();
+                *target = ((db + DYNAMIC_RANGE) / DYNAMIC_RANGE).clamp(0.0, 1.0);
+            }
+        }
+
+        if self.levels.len() != bands {
+            self.levels = vec![0.0; bands];
+        }
+        for (level, target) in self.levels.iter_mut().zip(targets) {
+            *level = target.max(*level - FALL);
+        }
+
+        &self.levels
+    }
+
+    // Up to `count` samples from -1 to 1, starting where the wave crosses zero going up
+    // so that it stands still on steady sounds
+    pub fn waveform(&self, count: usize) -> Vec<f32> {
+        let (samples, _) = self.last_samples();
+        let count = count.min(samples.len());
+        let start = (1..samples.len() - count + 1)
+            .find(|&i| samples[i - 1] < 0.0 && samples[i] >= 0.0)
+            .unwrap_or(0);
+
+        samples[start..start + count].to_vec()
+    }
+}
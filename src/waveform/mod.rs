@@ -0,0 +1,190 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use rodio::Source;
+
+use crate::file_ops::get_audio_source;
+use crate::music::Music;
+
+// Loudness is measured over windows this long
+const WINDOW: Duration = Duration::from_millis(50);
+// Envelopes of the last songs played are kept
+const CACHE_SIZE: usize = 16;
+
+// RMS loudness of every window of the decoded file, scaled so that the loudest is 1,
+// None once `cancel` is set
+pub fn envelope(path: &str, cancel: &AtomicBool) -> Option<Vec<f32>> {
+    let source = get_audio_source(path).ok()?;
+    let channels = source.channels().max(1) as usize;
+    let window = ((source.sample_rate() as f32 * WINDOW.as_secs_f32()) as usize * channels).max(1);
+
+    let mut envelope: Vec<f32> = Vec::new();
+    let mut sum = 0.0;
+    let mut count = 0;
+    for sample in source.convert_samples::<f32>() {
+        sum += sample * sample;
+        count += 1;
+        if count == window {
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+            envelope.push((sum / count as f32).sqrt());
+            sum = 0.0;
+            count = 0;
+        }
+    }
+    if count > 0 {
+        envelope.push((sum / count as f32).sqrt());
+    }
+
+    let loudest = envelope.iter().copied().fold(0.0, f32::max);
+    if loudest > 0.0 {
+        envelope.iter_mut().for_each(|level| *level /= loudest);
+    }
+
+    Some(envelope)
+}
+
+// A song decoded in a background thread, and the flag that stops it
+struct Pending {
+    path: String,
+    receiver: Receiver<Option<Vec<f32>>>,
+    cancel: Arc<AtomicBool>,
+}
+
+// Envelopes of the playing music, computed in a background thread the first time it plays
+pub struct Waveforms {
+    // None when the file could not be decoded
+    envelopes: HashMap<String, Option<Vec<f32>>>,
+    order: VecDeque<String>,
+    pending: Option<Pending>,
+}
+
+impl Waveforms {
+    pub fn new() -> Waveforms {
+        Waveforms {
+            envelopes: HashMap::new(),
+            order: VecDeque::new(),
+            pending: None,
+        }
+    }
+
+    pub fn update(&mut self, playing_music: Option<&Music>) {
+        if let Some(pending) = &self.pending {
+            if let Ok(envelope) = pending.receiver.try_recv() {
+                let path = pending.path.clone();
+                self.pending = None;
+                self.insert(path, envelope);
+            }
+        }
+
+        let path = match playing_music {
+            Some(music) => &music.path,
+            None => {
+                self.cancel();
+                return;
+            }
+        };
+        let computing = matches!(&self.pending, Some(pending) if pending.path == *path);
+        if computing || self.envelopes.contains_key(path) {
+            return;
+        }
+
+        // A song that is no longer playing is not decoded any further
+        self.cancel();
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let thread_path = path.clone();
+        let thread_cancel = Arc::clone(&cancel);
+        thread::spawn(move || {
+            let _ = sender.send(envelope(&thread_path, &thread_cancel));
+        });
+        self.pending = Some(Pending {
+            path: path.clone(),
+            receiver,
+            cancel,
+        });
+    }
+
+    fn cancel(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            pending.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn insert(&mut self, path: String, envelope: Option<Vec<f32>>) {
+        if self.order.len() >= CACHE_SIZE {
+            if let Some(oldest) = self.order.pop_front() {
+                self.envelopes.remove(&oldest);
+            }
+        }
+        self.order.push_back(path.clone());
+        self.envelopes.insert(path, envelope);
+    }
+
+    pub fn envelope(&self, path: &str) -> Option<&[f32]> {
+        self.envelopes.get(path)?.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    // A mono 16 bit WAV at 8 kHz, 400 samples make a window
+    fn write_wav(name: &str, samples: &[i16]) -> String {
+        let mut data = Vec::new();
+        let size = samples.len() as u32 * 2;
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&(36 + size).to_le_bytes());
+        data.extend_from_slice(b"WAVEfmt ");
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&8000u32.to_le_bytes());
+        data.extend_from_slice(&16000u32.to_le_bytes());
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&16u16.to_le_bytes());
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&size.to_le_bytes());
+        for sample in samples {
+            data.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        let path =
+            std::env::temp_dir().join(format!("music_player_{}_{}.wav", name, std::process::id()));
+        fs::write(&path, data).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn loudness_of_every_window() {
+        let mut samples = vec![0i16; 400];
+        samples.extend(vec![1000i16; 400]);
+        samples.extend(vec![-500i16; 200]);
+        let path = write_wav("envelope", &samples);
+        let envelope = envelope(&path, &AtomicBool::new(false));
+        fs::remove_file(&path).unwrap();
+
+        let envelope = envelope.unwrap();
+        assert_eq!(envelope.len(), 3);
+        assert_eq!(envelope[0], 0.0);
+        assert_eq!(envelope[1], 1.0);
+        assert!((envelope[2] - 0.5).abs() < 0.001);
+    }
+
+    #[test]
+    fn cancelled() {
+        let path = write_wav("cancelled", &[100; 800]);
+        let cancelled = envelope(&path, &AtomicBool::new(true));
+        fs::remove_file(&path).unwrap();
+
+        assert!(cancelled.is_none());
+    }
+}